@@ -0,0 +1,346 @@
+//! The types shared between the game and the AI scripts.
+//!
+//! Everything in here crosses an `extern "C"` boundary, so every type is
+//! `#[repr(C)]` and nothing owns heap memory. The game depends on this
+//! crate directly, scripts compiled with plain `rustc` get the same file
+//! through `src/script/structs.rs`.
+//!
+//! A script library must export:
+//! * `add(a: isize, b: isize) -> isize` returning `a + b`, used to check that the library works at all
+//! * `script_abi_version() -> u32` returning `SCRIPT_ABI_VERSION`
+//! * `calculate_move(context: &GameContext, out: &mut InputState) -> MoveStatus`,
+//!   which should wrap its logic in `catch_move`
+//!
+//! A panic that unwinds out of an `extern "C"` function aborts the process,
+//! and the game can't catch it on its side. Only the `catch_*` helpers
+//! here, or running the script with `--out-of-process`, keep a panicking
+//! script from taking the game down with it.
+//!
+//! A script that wants to remember things between ticks exports `init`
+//! as well, which makes it a stateful script:
+//! * `init(info: &MatchInfo) -> *mut c_void`, called before its first
+//!   move of a match, returns the script's state (null if it failed)
+//! * `calculate_move(state: *mut c_void, context: &GameContext, out: &mut InputState) -> MoveStatus`
+//!   instead of the stateless one
+//! * `on_point_scored(state: *mut c_void, point: &PointScored) -> MoveStatus`,
+//!   optional, called after every point
+//! * `destroy(state: *mut c_void)`, called at the end of the match
+//!
+//! `new_state`, `state_mut` and `drop_state` box and unbox the state,
+//! `catch_event` is `catch_move` for `on_point_scored`.
+//!
+//! The rules of the match (sizes, speeds, how many balls) are in
+//! `GameContext::rules` and `MatchInfo::rules`; they can change between
+//! matches, so don't count on the defaults.
+//!
+//! Scripts play as if they were always on the left: `GameContext::view`
+//! is mirrored for the right player, and the `InputState` a script
+//! returns is mirrored back by the game. The unmirrored game is there
+//! too, in `GameContext::game`.
+
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Bump this whenever the layout of anything in this file changes.
+/// The game refuses to call `calculate_move` in a script built against
+/// another version.
+pub const SCRIPT_ABI_VERSION: u32 = 5;
+
+pub type AddFunc = unsafe extern "C" fn(isize, isize) -> isize;
+pub type AbiVersionFunc = unsafe extern "C" fn() -> u32;
+pub type CalculateMoveFunc = unsafe extern "C" fn(&GameContext, &mut InputState) -> MoveStatus;
+pub type InitFunc = unsafe extern "C" fn(&MatchInfo) -> *mut c_void;
+pub type StatefulMoveFunc = unsafe extern "C" fn(*mut c_void, &GameContext, &mut InputState) -> MoveStatus;
+pub type PointScoredFunc = unsafe extern "C" fn(*mut c_void, &PointScored) -> MoveStatus;
+pub type DestroyFunc = unsafe extern "C" fn(*mut c_void);
+
+/// How a call to `calculate_move` went.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveStatus {
+    Ok,
+    /// The script panicked; `out` is left untouched.
+    Panicked,
+}
+
+/// Runs the move logic of a script and writes the result to `out`.
+/// A panic must never unwind out of an `extern "C"` function, that
+/// aborts the whole game, so it is caught here and reported as
+/// `MoveStatus::Panicked`.
+pub fn catch_move<F: FnOnce() -> InputState>(out: &mut InputState, f: F) -> MoveStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(input) => {
+            *out = input;
+            MoveStatus::Ok
+        }
+        Err(_) => MoveStatus::Panicked,
+    }
+}
+
+/// Like `catch_move`, for calls that don't make a move.
+pub fn catch_event<F: FnOnce()>(f: F) -> MoveStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => MoveStatus::Ok,
+        Err(_) => MoveStatus::Panicked,
+    }
+}
+
+/// Boxes the state `f` makes, for `init` to return. A panic in `f`
+/// gives a null state, which tells the game that `init` failed.
+pub fn new_state<S, F: FnOnce() -> S>(f: F) -> *mut c_void {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(state) => Box::into_raw(Box::new(state)) as *mut c_void,
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// The state behind the pointer `init` returned.
+///
+/// # Safety
+/// `state` has to come from `new_state::<S>` and not be dropped yet.
+pub unsafe fn state_mut<'a, S>(state: *mut c_void) -> &'a mut S {
+    &mut *(state as *mut S)
+}
+
+/// Frees the state, for `destroy`.
+///
+/// # Safety
+/// `state` has to come from `new_state::<S>` and not be dropped yet.
+pub unsafe fn drop_state<S>(state: *mut c_void) {
+    if !state.is_null() {
+        let state = Box::from_raw(state as *mut S);
+        let _ = panic::catch_unwind(AssertUnwindSafe(move || drop(state)));
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhysType {
+    Player,
+    Ball
+}
+
+/// A player or a ball, as seen by a script.
+/// `pos` is `[x, y]` in world coordinates: the origin is the center of
+/// the field and +y is up.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PhysObject {
+    pub tag: PhysType,
+    pub id: f32,
+    pub hold: f32,
+    pub pos: [f32; 2],
+    pub x_velocity: f32,
+    pub y_velocity: f32,
+    pub bbox_size: f32,
+}
+
+impl PhysObject {
+    /// The object as seen from the other side of the field.
+    pub fn mirrored(&self) -> PhysObject {
+        PhysObject {
+            pos: [-self.pos[0], self.pos[1]],
+            x_velocity: -self.x_velocity,
+            ..*self
+        }
+    }
+}
+
+/// What a player (or a script) wants to do this tick.
+///
+/// Letting go of `holdball` drops the ball with the player's own velocity.
+/// `throw` throws it instead: towards `aim` (straight ahead if that is
+/// zero), faster the higher `charge` is, from 0 to 1. A player that
+/// throws and keeps `holdball` doesn't catch its own ball again.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputState {
+    pub xaxis1pos: f32,
+    pub xaxis1neg: f32,
+    pub yaxis1pos: f32,
+    pub yaxis1neg: f32,
+    pub holdball: bool,
+    pub throw: bool,
+    /// `[x, y]`, any length.
+    pub aim: [f32; 2],
+    pub charge: f32,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        InputState {
+            xaxis1pos: 0.0,
+            xaxis1neg: 0.0,
+            yaxis1pos: 0.0,
+            yaxis1neg: 0.0,
+            holdball: false,
+            throw: false,
+            aim: [0.0, 0.0],
+            charge: 0.0,
+        }
+    }
+}
+
+impl InputState {
+    /// The same input for the other side of the field: left and right swap.
+    pub fn mirrored(&self) -> InputState {
+        InputState {
+            xaxis1pos: -self.xaxis1neg,
+            xaxis1neg: -self.xaxis1pos,
+            aim: [-self.aim[0], self.aim[1]],
+            ..*self
+        }
+    }
+}
+
+/// The numbers a match is played with. The game reads them from a rules
+/// file and the command line; scripts get them to read only.
+/// Distances are in pixels and times in seconds.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct GameRules {
+    pub balls_per_side: u32,
+    /// Space between two balls of a column, and between the columns.
+    pub ball_spacing: f32,
+    pub player_radius: f32,
+    pub ball_radius: f32,
+    /// How far in front of its player a held ball is.
+    pub hold_offset: f32,
+    /// How much further than touching a player reaches to pick up a ball.
+    pub pickup_reach: f32,
+    /// Per second squared.
+    pub player_acceleration: f32,
+    /// How fast a player slows down by itself, per second squared.
+    pub player_friction: f32,
+    pub max_player_speed: f32,
+    /// How fast a ball slows down, per second squared.
+    pub ball_drag: f32,
+    /// Speed of a throw without any charge.
+    pub min_throw_speed: f32,
+    /// Speed of a fully charged throw, and the speed limit of balls.
+    pub max_throw_speed: f32,
+    /// Only the ratio between the masses matters.
+    pub player_mass: f32,
+    pub ball_mass: f32,
+    /// How much of their speed towards each other two objects keep,
+    /// pointing away from each other, after they collide. 1 is elastic.
+    pub restitution: f32,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            balls_per_side: 6,
+            ball_spacing: 72.0,
+            player_radius: 24.0,
+            ball_radius: 24.0,
+            hold_offset: 32.0,
+            pickup_reach: 2.0,
+            player_acceleration: 480.0,
+            player_friction: 60.0,
+            max_player_speed: 200.0,
+            ball_drag: 20.0,
+            min_throw_speed: 150.0,
+            max_throw_speed: 450.0,
+            player_mass: 4.0,
+            ball_mass: 1.0,
+            restitution: 0.8,
+        }
+    }
+}
+
+/// The whole game, in world coordinates.
+/// The balls are borrowed from the game and are only valid during the call.
+#[repr(C)]
+#[derive(Debug)]
+pub struct GameSnapshot {
+    pub player1: PhysObject,
+    pub player2: PhysObject,
+    pub balls: *const PhysObject,
+    pub balls_len: usize,
+    pub score1: i32,
+    pub score2: i32,
+    pub screen_width: f32,
+    pub screen_height: f32,
+    pub input1: InputState,
+    pub input2: InputState,
+}
+
+impl GameSnapshot {
+    pub fn balls(&self) -> &[PhysObject] {
+        if self.balls.is_null() || self.balls_len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.balls, self.balls_len) }
+    }
+}
+
+/// The game from the side of the player a script controls, mirrored
+/// for the right player so that `me` always defends the left half
+/// (negative x) and attacks towards +x.
+/// The balls are borrowed from the game and are only valid during the call.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SideView {
+    pub me: PhysObject,
+    pub opponent: PhysObject,
+    pub balls: *const PhysObject,
+    pub balls_len: usize,
+    pub my_score: i32,
+    pub opponent_score: i32,
+    /// What each player did last tick, mirrored like everything else.
+    pub my_input: InputState,
+    pub opponent_input: InputState,
+}
+
+impl SideView {
+    pub fn balls(&self) -> &[PhysObject] {
+        if self.balls.is_null() || self.balls_len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.balls, self.balls_len) }
+    }
+}
+
+/// Everything `calculate_move` gets once per tick.
+#[repr(C)]
+#[derive(Debug)]
+pub struct GameContext {
+    pub abi_version: u32,
+    /// Whether the script controls player 1, on the left.
+    pub p1: bool,
+    /// The game as the script's own player sees it. The `InputState` a
+    /// script returns is read in these coordinates.
+    pub view: SideView,
+    /// The game as it is.
+    pub game: GameSnapshot,
+    pub rules: GameRules,
+}
+
+/// What a stateful script is told about a match before it starts.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MatchInfo {
+    /// Whether the script controls player 1, on the left.
+    pub p1: bool,
+    pub field_width: f32,
+    pub field_height: f32,
+    /// The seed of the match, for scripts that want to be as
+    /// reproducible as the game.
+    pub seed: u64,
+    pub rules: GameRules,
+}
+
+/// Passed to `on_point_scored`, after the field has been reset.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PointScored {
+    /// Whether the script's own player scored.
+    pub i_scored: bool,
+    pub my_score: i32,
+    pub opponent_score: i32,
+}
@@ -0,0 +1,284 @@
+//! The command line of the game.
+//!
+//! ```text
+//! framslov-melvinj-game [play] [P1] [P2] [options]
+//! framslov-melvinj-game headless [P1] [P2] [options]
+//! framslov-melvinj-game tournament [SCRIPT...] [options]
+//! framslov-melvinj-game list-scripts [options]
+//! framslov-melvinj-game ladder [SCRIPT] [options]
+//! framslov-melvinj-game replay FILE [options]
+//! framslov-melvinj-game rules [options]
+//! ```
+//!
+//! Options are `--name=value` or plain `--flag`, and may come anywhere.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ladder::LADDER_FILE;
+use crate::rules;
+use crate::script_build::SCRIPT_DIR;
+use crate::scripting::{OverrunPenalty, ScriptSettings};
+use crate::simulation::{GameConfig, WinCondition};
+
+pub const USAGE: &str = "\
+Usage:
+  framslov-melvinj-game [play] [P1] [P2] [options]    play in a window
+  framslov-melvinj-game headless [P1] [P2] [options]  play one match without a window
+  framslov-melvinj-game tournament [SCRIPT...] [options]
+                                  every script plays every other, on both sides,
+                                  once per seed; all scripts if none are given
+  framslov-melvinj-game list-scripts [options]        show the scripts there are
+  framslov-melvinj-game ladder [SCRIPT] [options]     show the rating ladder, or how
+                                                      the rating of one script went
+  framslov-melvinj-game replay FILE                   watch a recorded match
+  framslov-melvinj-game rules [options]               print the rules as a rules file
+  framslov-melvinj-game help
+
+A player is the exact name of a script, or `human` for the keyboard (the default).
+Human players do nothing in headless matches.
+
+Options:
+  --width=PIXELS          width of the field (640)
+  --height=PIXELS         height of the field (480)
+  --balls=N               balls on each side (6)
+  --score-limit=N         first to N points wins, 0 plays forever (5)
+  --time-limit=SECONDS    whoever is ahead after this much game time wins
+  --best-of=N             at most N points are played, more than half wins
+  --seed=N                seed for everything random, to play a match again (random)
+  --substeps=N            physics steps per tick, more is more precise (1)
+  --rules=FILE            read sizes, speeds and masses from a TOML rules file
+  --rule=NAME=VALUE       change one rule, over the rules file
+  --script-dir=DIR        where the scripts are (src/script)
+  --max-time=SECONDS      headless matches end after this much game time (300)
+  --json                  print headless results as JSON
+  --record=PATH           save matches as replays: a file, or a folder for tournaments
+  --seeds=N               seeds every tournament pairing is played with (3)
+  --export=FILE           write tournament results, as CSV for .csv files, else JSON
+  --ladder=FILE           where the ratings of the scripts are kept (ladder.json)
+  --unrated               don't rate the matches played
+  --out-of-process        run each script in its own process
+  --time-budget=MS        time a script may think each tick (2)
+  --forfeit-on-overrun    scripts that keep going over budget forfeit";
+
+/// Who plays a side.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerChoice {
+    Human,
+    Script(String),
+}
+
+pub const HUMAN_PLAYER: &str = "human";
+
+impl PlayerChoice {
+    /// A missing argument or `human` is a human player, anything else is
+    /// taken as the name of a script.
+    pub fn parse(arg: Option<&str>) -> PlayerChoice {
+        match arg {
+            None | Some(HUMAN_PLAYER) => PlayerChoice::Human,
+            Some(name) => PlayerChoice::Script(name.to_string()),
+        }
+    }
+
+    pub fn script_name(&self) -> Option<&str> {
+        match self {
+            PlayerChoice::Human => None,
+            PlayerChoice::Script(name) => Some(name),
+        }
+    }
+
+    /// Checks that a script player is one of `scripts`, by exact name.
+    pub fn check(&self, scripts: &[&str]) -> Result<(), String> {
+        match self.script_name() {
+            Some(name) if !scripts.contains(&name) => Err(format!("There is no script called {}", name)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Play { player1: PlayerChoice, player2: PlayerChoice },
+    Headless { player1: PlayerChoice, player2: PlayerChoice },
+    /// An empty list means every script.
+    Tournament { scripts: Vec<String> },
+    ListScripts,
+    /// The whole ladder, or the history of one script.
+    Ladder { script: Option<String> },
+    Replay { path: PathBuf },
+    /// Print the rules the options add up to.
+    Rules,
+    Help,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub command: Command,
+    pub game: GameConfig,
+    pub script_dir: PathBuf,
+    pub scripts: ScriptSettings,
+    /// Game time after which a headless match is stopped, in seconds.
+    pub max_time: f32,
+    /// Print results as JSON instead of text.
+    pub json: bool,
+    /// Where to save replays of the matches played.
+    pub record: Option<PathBuf>,
+    /// How many seeds a tournament plays every pairing with.
+    pub seeds: u32,
+    /// Where to write the results of a tournament.
+    pub export: Option<PathBuf>,
+    /// The rating ladder that matches between scripts update.
+    pub ladder: PathBuf,
+    pub rated: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            command: Command::Play { player1: PlayerChoice::Human, player2: PlayerChoice::Human },
+            //A new seed every run, unless one is given
+            game: GameConfig { seed: rand::random(), ..GameConfig::default() },
+            script_dir: PathBuf::from(SCRIPT_DIR),
+            scripts: ScriptSettings::default(),
+            max_time: 300.0,
+            json: false,
+            record: None,
+            seeds: 3,
+            export: None,
+            ladder: PathBuf::from(LADDER_FILE),
+            rated: true,
+        }
+    }
+}
+
+/// Parses the arguments, without the program name.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+    //The rules file comes first and the single rules over it, wherever they are
+    let mut rules_file = None;
+    let mut rule_changes = Vec::new();
+
+    for arg in args {
+        let option = match arg.strip_prefix("--") {
+            Some(option) => option,
+            None => {
+                positional.push(arg);
+                continue;
+            }
+        };
+        let (name, value) = match option.find('=') {
+            Some(split) => (&option[..split], Some(&option[split + 1..])),
+            None => (option, None),
+        };
+        match (name, value) {
+            ("width", Some(value)) => options.game.width = finite(name, value)?,
+            ("height", Some(value)) => options.game.height = finite(name, value)?,
+            ("balls", Some(value)) => {
+                let _: u32 = parse_value(name, value)?;
+                rule_changes.push(("balls_per_side".to_string(), value.to_string()));
+            }
+            ("score-limit", Some("0")) => options.game.win_condition = WinCondition::Endless,
+            ("score-limit", Some(value)) => options.game.win_condition = WinCondition::FirstTo(positive(name, value)?),
+            ("time-limit", Some(value)) => options.game.win_condition = WinCondition::TimeLimit(finite(name, value)?),
+            ("best-of", Some(value)) => options.game.win_condition = WinCondition::BestOf(positive(name, value)?),
+            ("seed", Some(value)) => options.game.seed = parse_value(name, value)?,
+            ("substeps", Some(value)) => options.game.substeps = positive(name, value)?,
+            ("rules", Some(value)) => rules_file = Some(PathBuf::from(value)),
+            ("rule", Some(value)) => match value.find('=') {
+                Some(split) => rule_changes.push((value[..split].to_string(), value[split + 1..].to_string())),
+                None => return Err(format!("--rule needs a name and a value, like --rule=ball_drag=40, not {}", value)),
+            },
+            ("script-dir", Some(value)) => options.script_dir = PathBuf::from(value),
+            ("record", Some(value)) => options.record = Some(PathBuf::from(value)),
+            ("seeds", Some(value)) => options.seeds = positive(name, value)?,
+            ("export", Some(value)) => options.export = Some(PathBuf::from(value)),
+            ("ladder", Some(value)) => options.ladder = PathBuf::from(value),
+            ("max-time", Some(value)) => options.max_time = finite(name, value)?,
+            ("time-budget", Some(value)) => {
+                let ms: f64 = finite(name, value)?;
+                options.scripts.budget.per_call = Duration::try_from_secs_f64(ms / 1000.0)
+                    .map_err(|_| format!("--{} is too long: {}", name, value))?;
+            }
+            ("json", None) => options.json = true,
+            ("unrated", None) => options.rated = false,
+            ("out-of-process", None) => options.scripts.out_of_process = true,
+            ("forfeit-on-overrun", None) => options.scripts.budget.penalty = OverrunPenalty::Forfeit,
+            ("list-scripts", None) => positional.insert(0, "list-scripts".to_string()),
+            ("help", None) => positional.insert(0, "help".to_string()),
+            _ => return Err(format!("Unknown option --{}", option)),
+        }
+    }
+
+    if let Some(path) = &rules_file {
+        options.game.rules = rules::load(path)?;
+    }
+    for (rule, value) in rule_changes {
+        rules::set(&mut options.game.rules, &rule, &value)?;
+    }
+    rules::check(&options.game.rules, options.game.width, options.game.height)?;
+
+    let mut positional = positional.into_iter().peekable();
+    let subcommand = match positional.peek().map(String::as_str) {
+        Some("play") | Some("headless") | Some("tournament") | Some("list-scripts") | Some("ladder") | Some("replay") | Some("rules") | Some("help") => positional.next(),
+        _ => None,
+    };
+    let rest: Vec<String> = positional.collect();
+    let players = |rest: &[String]| -> Result<(PlayerChoice, PlayerChoice), String> {
+        if rest.len() > 2 {
+            return Err(format!("Too many players: {}", rest.join(" ")));
+        }
+        let player = |index: usize| PlayerChoice::parse(rest.get(index).map(String::as_str));
+        Ok((player(0), player(1)))
+    };
+
+    options.command = match subcommand.as_deref() {
+        None | Some("play") => {
+            let (player1, player2) = players(&rest)?;
+            Command::Play { player1, player2 }
+        }
+        Some("headless") => {
+            let (player1, player2) = players(&rest)?;
+            Command::Headless { player1, player2 }
+        }
+        Some("tournament") => Command::Tournament { scripts: rest },
+        Some("list-scripts") => Command::ListScripts,
+        Some("ladder") => match rest.as_slice() {
+            [] => Command::Ladder { script: None },
+            [script] => Command::Ladder { script: Some(script.clone()) },
+            _ => return Err(format!("ladder takes at most one script: {}", rest.join(" "))),
+        },
+        Some("rules") => Command::Rules,
+        Some("replay") => match rest.as_slice() {
+            [path] => Command::Replay { path: PathBuf::from(path) },
+            _ => return Err("replay needs exactly one replay file".to_string()),
+        },
+        _ => Command::Help,
+    };
+    Ok(options)
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value for --{}: {}", name, value))
+}
+
+fn positive<T: FromStr + PartialOrd + Default>(name: &str, value: &str) -> Result<T, String> {
+    let parsed = parse_value(name, value)?;
+    if parsed > T::default() {
+        Ok(parsed)
+    } else {
+        Err(format!("--{} has to be more than 0, not {}", name, value))
+    }
+}
+
+/// A positive number that isn't infinite, for the options in pixels
+/// and seconds. `inf` parses, and so does anything too big for an `f32`.
+fn finite<T: FromStr + PartialOrd + Default + Copy + Into<f64>>(name: &str, value: &str) -> Result<T, String> {
+    let parsed: T = positive(name, value)?;
+    if parsed.into().is_finite() {
+        Ok(parsed)
+    } else {
+        Err(format!("--{} has to be a finite number, not {}", name, value))
+    }
+}
@@ -0,0 +1,171 @@
+//! Matches without a window, run as fast as the machine allows.
+//! A side without a script stands still, there is no keyboard here.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::HUMAN_PLAYER;
+use crate::replay::Replay;
+use crate::scripting::{ScriptError, ScriptPlayer};
+use crate::simulation::{GameConfig, InputState, Outcome, Side, Simulation};
+
+/// How a headless match ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub score1: i32,
+    pub score2: i32,
+    pub outcome: Outcome,
+    /// False if the match was stopped at the time cap before its win
+    /// condition decided it; the outcome then goes by the score.
+    pub finished: bool,
+    /// Game time played, in seconds.
+    pub game_time: f32,
+    /// The seed the match was played with.
+    pub seed: u64,
+    /// Why the script of player 1 stopped playing, if it did.
+    pub failure1: Option<ScriptError>,
+    pub failure2: Option<ScriptError>,
+    /// False if a script could not even be loaded. The match was never
+    /// played then: that script forfeits it and it isn't rated.
+    pub played: bool,
+}
+
+impl MatchResult {
+    /// A match that couldn't start because a script failed to load; the
+    /// failures say which.
+    pub fn not_played(seed: u64, failure1: Option<ScriptError>, failure2: Option<ScriptError>) -> MatchResult {
+        MatchResult {
+            score1: 0,
+            score2: 0,
+            outcome: forfeit(&failure1, &failure2).unwrap_or(Outcome::Draw),
+            finished: true,
+            game_time: 0.0,
+            seed,
+            failure1,
+            failure2,
+            played: false,
+        }
+    }
+}
+
+/// Who wins because a script failed, if one did.
+fn forfeit(failure1: &Option<ScriptError>, failure2: &Option<ScriptError>) -> Option<Outcome> {
+    match (failure1, failure2) {
+        (Some(_), Some(_)) => Some(Outcome::Draw),
+        (Some(_), None) => Some(Outcome::Winner(Side::Right)),
+        (None, Some(_)) => Some(Outcome::Winner(Side::Left)),
+        (None, None) => None,
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let outcome = match self.outcome {
+            Outcome::Winner(Side::Left) => "P1 wins",
+            Outcome::Winner(Side::Right) => "P2 wins",
+            Outcome::Draw => "draw",
+        };
+        write!(f, "{} - {}, {} after {:.1} s (seed {})", self.score1, self.score2, outcome, self.game_time, self.seed)?;
+        if !self.finished {
+            write!(f, " (stopped at the time cap)")?;
+        }
+        if !self.played {
+            write!(f, " (not played)")?;
+        }
+        match (&self.failure1, &self.failure2) {
+            (Some(_), Some(_)) => write!(f, " (both scripts failed)")?,
+            (Some(_), None) => write!(f, " (P1 forfeits)")?,
+            (None, Some(_)) => write!(f, " (P2 forfeits)")?,
+            (None, None) => (),
+        }
+        Ok(())
+    }
+}
+
+/// Plays a match until its win condition is met, or until `max_time`
+/// seconds of game time have passed, and records it as it goes.
+/// A script that fails forfeits: the match ends there and the other
+/// side wins, whatever the score. Stateful scripts are told about every
+/// point and about the end.
+pub fn run_match(
+    config: GameConfig,
+    mut script1: Option<&mut ScriptPlayer>,
+    mut script2: Option<&mut ScriptPlayer>,
+    max_time: f32,
+) -> (MatchResult, Replay) {
+    let name = |script: &Option<&mut ScriptPlayer>| script.as_ref().map_or(HUMAN_PLAYER, |script| script.name()).to_string();
+    let mut replay = Replay::new(config, &name(&script1), &name(&script2));
+    let mut sim = Simulation::with_config(config);
+    let mut failure1 = None;
+    let mut failure2 = None;
+
+    while sim.game.outcome().is_none() && sim.game.time < max_time && failure1.is_none() && failure2.is_none() {
+        let input1 = script_input(&mut script1, &mut failure1, &sim, true);
+        let input2 = script_input(&mut script2, &mut failure2, &sim, false);
+        replay.record(input1, input2);
+        if let Some(scorer) = sim.tick(input1, input2) {
+            script_point(&mut script1, &mut failure1, &sim, true, scorer);
+            script_point(&mut script2, &mut failure2, &sim, false, scorer);
+        }
+    }
+    for script in [script1, script2].iter_mut().flatten() {
+        script.end_match();
+    }
+
+    let game = &sim.game;
+    let outcome = forfeit(&failure1, &failure2).or_else(|| game.outcome());
+    let result = MatchResult {
+        score1: game.score1,
+        score2: game.score2,
+        outcome: outcome.unwrap_or_else(|| Outcome::by_score(game.score1, game.score2)),
+        finished: outcome.is_some(),
+        game_time: game.time,
+        seed: game.config.seed,
+        failure1,
+        failure2,
+        played: true,
+    };
+    (result, replay)
+}
+
+fn script_point(
+    script: &mut Option<&mut ScriptPlayer>,
+    failure: &mut Option<ScriptError>,
+    sim: &Simulation,
+    p1: bool,
+    scorer: Side,
+) {
+    if failure.is_some() {
+        return;
+    }
+    if let Some(script) = script {
+        if let Err(reason) = script.point_scored(&sim.game, p1, scorer) {
+            eprintln!("Script {} failed: {}", script.name(), reason);
+            *failure = Some(reason);
+        }
+    }
+}
+
+fn script_input(
+    script: &mut Option<&mut ScriptPlayer>,
+    failure: &mut Option<ScriptError>,
+    sim: &Simulation,
+    p1: bool,
+) -> InputState {
+    if failure.is_some() {
+        return InputState::default();
+    }
+    let script = match script {
+        Some(script) => script,
+        None => return InputState::default(),
+    };
+    match script.generate_input(&sim.game, p1) {
+        Ok(input) => input,
+        Err(reason) => {
+            eprintln!("Script {} failed: {}", script.name(), reason);
+            *failure = Some(reason);
+            InputState::default()
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Hot reloading of AI scripts.
+//!
+//! A `ScriptWatcher` polls the script folder on a background thread.
+//! When a script's sources change it is rebuilt there, and the new library
+//! is handed to the game, which swaps it in at the next point reset.
+//! The game loads every library from a copy of its own, in a folder for
+//! this process that goes away with the watcher. A library that is
+//! loaded can't be written over on Windows, so the rebuild would fail if
+//! the game played the one it builds to.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::script_build::{discover_scripts, BuildCache, ScriptLibrary, SCRIPT_CACHE_DIR};
+use crate::scripting::ScriptError;
+
+/// A script that changed on disk and was rebuilt, or failed to.
+#[derive(Debug)]
+pub struct ReloadEvent {
+    pub name: String,
+    pub result: Result<ScriptLibrary, ScriptError>,
+}
+
+pub struct ScriptWatcher {
+    events: Receiver<ReloadEvent>,
+    // Never sent on, the thread stops when it is dropped with the watcher
+    _stop: Sender<()>,
+}
+
+impl ScriptWatcher {
+    /// Starts watching `dir`, checking for changes every `interval`.
+    /// The thread stops at its next check once the watcher is dropped, or
+    /// when the rebuild it is busy with is done.
+    pub fn start(dir: PathBuf, interval: Duration) -> ScriptWatcher {
+        let (sender, events) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel();
+        thread::spawn(move || {
+            watch(&dir, interval, sender, stopped);
+            let _ = fs::remove_dir_all(reload_dir());
+        });
+        ScriptWatcher { events, _stop: stop }
+    }
+
+    /// All scripts rebuilt since the last call.
+    pub fn poll(&self) -> Vec<ReloadEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Drop for ScriptWatcher {
+    /// The game exits without waiting for the thread, so the copies are
+    /// deleted here too. Where a loaded library can't be deleted, like on
+    /// Windows, they are left behind.
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(reload_dir());
+    }
+}
+
+fn current_hashes(dir: &Path) -> HashMap<String, u64> {
+    let mut hashes = HashMap::new();
+    for (name, source) in discover_scripts(dir).unwrap_or_default() {
+        if let Ok(hash) = source.and_then(|source| source.source_hash().map_err(|e| ScriptError::Compile(e.to_string()))) {
+            hashes.insert(name, hash);
+        }
+    }
+    hashes
+}
+
+fn watch(dir: &Path, interval: Duration, sender: Sender<ReloadEvent>, stopped: Receiver<()>) {
+    // The scripts were just built by the game, so this is where we start from
+    let mut known = current_hashes(dir);
+    // Scripts with a broken manifest, so they are only reported once
+    let mut broken = HashSet::new();
+
+    loop {
+        if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+            return;
+        }
+        let sources = match discover_scripts(dir) {
+            Ok(sources) => sources,
+            Err(_) => continue,
+        };
+
+        for (name, source) in sources {
+            let source = match source {
+                Ok(source) => source,
+                Err(reason) => {
+                    known.remove(&name);
+                    if broken.insert(name.clone()) && sender.send(ReloadEvent { name, result: Err(reason) }).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            broken.remove(&name);
+            let hash = match source.source_hash() {
+                Ok(hash) => hash,
+                // Probably caught halfway through a save, try again next time
+                Err(_) => continue,
+            };
+            if known.get(&name) == Some(&hash) {
+                continue;
+            }
+            known.insert(name.clone(), hash);
+
+            let mut cache = BuildCache::open();
+            let result = source.build_cached(&mut cache).and_then(|library| private_copy(&library));
+            let _ = cache.save();
+            if sender.send(ReloadEvent { name, result }).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Where the copies of rebuilt libraries go, one folder per running game.
+fn reload_dir() -> PathBuf {
+    Path::new(SCRIPT_CACHE_DIR).join("reload").join(process::id().to_string())
+}
+
+/// Numbers the copies, so that every one gets a path of its own.
+static COPIES: AtomicU32 = AtomicU32::new(0);
+
+/// Copies a built library to a path of its own, to be loaded from there.
+/// The dynamic loader hands back the library it already has when asked
+/// for the same path twice, so a rebuilt script has to be loaded from
+/// somewhere new, and the build must be free to write over the original.
+pub fn private_copy(library: &ScriptLibrary) -> Result<ScriptLibrary, ScriptError> {
+    let dir = reload_dir();
+    let copy = COPIES.fetch_add(1, Ordering::Relaxed) + 1;
+    let file_name = format!("{}{}-{}{}", env::consts::DLL_PREFIX, library.name, copy, env::consts::DLL_SUFFIX);
+    let path = dir.join(file_name);
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::copy(&library.path, &path))
+        .map_err(|e| ScriptError::Load(format!("could not copy {}: {}", library.path.display(), e)))?;
+    Ok(ScriptLibrary {
+        path,
+        ..library.clone()
+    })
+}
@@ -0,0 +1,228 @@
+//! A rating ladder for scripts that lasts between runs.
+//!
+//! Every match between two scripts, headless, in a tournament or in the
+//! window, updates their Elo ratings in the ladder file. A script is
+//! rated per version: its name together with the hash of the sources it
+//! was built from. A new version starts from the rating of the version
+//! before it, so its history shows whether the change made it stronger.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::script_build::ScriptLibrary;
+use crate::simulation::{Outcome, Side};
+
+/// Where the ladder is kept unless told otherwise.
+pub const LADDER_FILE: &str = "ladder.json";
+/// The rating of a script that has never played.
+pub const START_RATING: f64 = 1500.0;
+/// How far one match can move a rating.
+const K_FACTOR: f64 = 32.0;
+
+/// One version of a script.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RatedScript {
+    pub name: String,
+    pub source_hash: String,
+}
+
+impl RatedScript {
+    pub fn new(library: &ScriptLibrary) -> RatedScript {
+        RatedScript {
+            name: library.name.clone(),
+            source_hash: library.source_hash.clone(),
+        }
+    }
+
+    /// The start of the hash, enough to tell versions apart.
+    pub fn version(&self) -> &str {
+        &self.source_hash[..self.source_hash.len().min(8)]
+    }
+}
+
+/// One rated match, as seen by one of the scripts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingChange {
+    /// Numbers the matches of the whole ladder, in order.
+    pub game: u64,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub opponent: RatedScript,
+    /// 1 for a win, 0.5 for a draw, 0 for a loss.
+    pub score: f64,
+    /// The rating after the match.
+    pub rating: f64,
+    pub change: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LadderEntry {
+    pub script: RatedScript,
+    pub rating: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub history: Vec<RatingChange>,
+}
+
+impl LadderEntry {
+    pub fn played(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ladder {
+    /// Rated matches so far.
+    pub games: u64,
+    pub entries: Vec<LadderEntry>,
+}
+
+/// The score `rating` is expected to make against `opponent`, between 0 and 1.
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+impl Ladder {
+    /// Reads the ladder at `path`. There not being one yet is an empty ladder.
+    pub fn open(path: &Path) -> io::Result<Ladder> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Ladder::default()),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    /// Rates a match between two scripts. A script playing itself
+    /// teaches nothing and isn't rated.
+    pub fn record(&mut self, player1: &RatedScript, player2: &RatedScript, outcome: Outcome) {
+        if player1 == player2 {
+            return;
+        }
+        let score1 = match outcome {
+            Outcome::Winner(Side::Left) => 1.0,
+            Outcome::Winner(Side::Right) => 0.0,
+            Outcome::Draw => 0.5,
+        };
+        let index1 = self.entry(player1);
+        let index2 = self.entry(player2);
+        let rating1 = self.entries[index1].rating;
+        let rating2 = self.entries[index2].rating;
+        let change1 = K_FACTOR * (score1 - expected_score(rating1, rating2));
+
+        self.games += 1;
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let games = self.games;
+        let mut update = |index: usize, opponent: &RatedScript, score: f64, change: f64| {
+            let entry = &mut self.entries[index];
+            entry.rating += change;
+            match score {
+                s if s > 0.5 => entry.wins += 1,
+                s if s < 0.5 => entry.losses += 1,
+                _ => entry.draws += 1,
+            }
+            entry.history.push(RatingChange {
+                game: games,
+                time,
+                opponent: opponent.clone(),
+                score,
+                rating: entry.rating,
+                change,
+            });
+        };
+        update(index1, player2, score1, change1);
+        update(index2, player1, 1.0 - score1, -change1);
+    }
+
+    /// The index of a script's entry, made if it has none yet.
+    fn entry(&mut self, script: &RatedScript) -> usize {
+        if let Some(index) = self.entries.iter().position(|entry| entry.script == *script) {
+            return index;
+        }
+        //A new version carries on from the last version that played
+        let rating = self.entries.iter()
+            .filter(|entry| entry.script.name == script.name)
+            .filter_map(|entry| entry.history.last().map(|change| (change.game, entry.rating)))
+            .max_by_key(|(game, _)| *game)
+            .map_or(START_RATING, |(_, rating)| rating);
+        self.entries.push(LadderEntry {
+            script: script.clone(),
+            rating,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            history: Vec::new(),
+        });
+        self.entries.len() - 1
+    }
+
+    /// Every version of every script, best first, as a table for the terminal.
+    pub fn table(&self) -> String {
+        let mut entries: Vec<&LadderEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap_or(std::cmp::Ordering::Equal));
+        let width = entries.iter().map(|entry| entry.script.name.len()).max().unwrap_or(0).max(6);
+        let mut table = format!("{:>4}  {:<width$}  {:<8} {:>6} {:>4} {:>4} {:>4} {:>4}\n", "#", "Script", "Version", "Rating", "P", "W", "D", "L", width = width);
+        for (rank, entry) in entries.iter().enumerate() {
+            let _ = writeln!(
+                table,
+                "{:>4}  {:<width$}  {:<8} {:>6.0} {:>4} {:>4} {:>4} {:>4}",
+                rank + 1,
+                entry.script.name,
+                entry.script.version(),
+                entry.rating,
+                entry.played(),
+                entry.wins,
+                entry.draws,
+                entry.losses,
+                width = width,
+            );
+        }
+        table
+    }
+
+    /// How the rating of every version of the script `name` went, oldest
+    /// version first. `None` if it never played a rated match.
+    pub fn history(&self, name: &str) -> Option<String> {
+        let mut entries: Vec<&LadderEntry> = self.entries.iter().filter(|entry| entry.script.name == name).collect();
+        if entries.is_empty() {
+            return None;
+        }
+        entries.sort_by_key(|entry| entry.history.first().map_or(0, |change| change.game));
+        let mut text = String::new();
+        for entry in entries {
+            let _ = writeln!(text, "{} {}: {:.0} after {} matches", name, entry.script.version(), entry.rating, entry.played());
+            for change in &entry.history {
+                let result = match change.score {
+                    s if s > 0.5 => "won",
+                    s if s < 0.5 => "lost",
+                    _ => "drew",
+                };
+                let _ = writeln!(
+                    text,
+                    "  #{:<5} {} against {} {}, {:.0} ({:+.0})",
+                    change.game,
+                    result,
+                    change.opponent.name,
+                    change.opponent.version(),
+                    change.rating,
+                    change.change,
+                );
+            }
+        }
+        Some(text)
+    }
+}
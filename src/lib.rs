@@ -0,0 +1,17 @@
+//! Space Dodgeball as a library.
+//! The window and everything drawn lives in `main.rs`; the rules and
+//! physics live here so they can run without ggez.
+
+pub mod cli;
+pub mod headless;
+pub mod hot_reload;
+pub mod ladder;
+pub mod replay;
+pub mod rules;
+pub mod physics;
+pub mod script_build;
+pub mod scripting;
+pub mod simulation;
+pub mod tournament;
+
+pub use simulation::{GameConfig, GameRules, GameState, InputState, Outcome, PhysObject, PhysType, Side, Simulation, WinCondition, TICK};
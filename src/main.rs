@@ -3,7 +3,6 @@
 //! The idea is that this game is simple but still
 //! non-trivial enough to be interesting.
 
-use ggez;
 // use ggez::audio;
 // use ggez::audio::SoundSource;
 use ggez::conf;
@@ -15,263 +14,28 @@ use ggez::{Context, ContextBuilder, GameResult};
 
 use std::env;
 use std::path;
-use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::process;
 
 // use std::fs::File;
 // use std::io::prelude::*;
-use std::process::Command;
-use libloading::{Library, Symbol};
-use std::fs::{self};
-use std::path::Path;
-
-type AddFunc = unsafe fn(isize, isize) -> isize;
-type AIFunc = unsafe fn(&GameState, bool) -> InputState;
+use std::time::{Duration, Instant};
+
+use framslov_melvinj_game::cli::{self, Command, Options, PlayerChoice, HUMAN_PLAYER, USAGE};
+use framslov_melvinj_game::hot_reload::{private_copy, ReloadEvent, ScriptWatcher};
+use framslov_melvinj_game::ladder::{Ladder, RatedScript};
+use framslov_melvinj_game::replay::{Replay, ReplayPlayer};
+use framslov_melvinj_game::rules;
+use framslov_melvinj_game::script_build::{discover_scripts, BuildCache, ScriptLibrary, ScriptSource};
+use framslov_melvinj_game::scripting::{self, ScriptError, ScriptPlayer, ScriptSettings};
+use framslov_melvinj_game::headless;
+use framslov_melvinj_game::tournament::Tournament;
+use framslov_melvinj_game::{GameConfig, GameState, InputState, Outcome, PhysObject, PhysType, Side, Simulation, WinCondition, TICK};
 
 type Point2 = na::Point2<f32>;
 
-/// *********************************************************************
-/// Now we define our PhysObjects.
-/// A PhysObject is anything in the game world.
-/// **********************************************************************
-#[derive(Debug)]
-enum PhysType {
-    Player,
-    Ball
-}
-
-#[derive(Debug)]
-struct PhysObject {
-    tag: PhysType,
-    id: f32,
-    hold: f32,
-    pos: (f32, f32),
-    x_velocity: f32,
-    y_velocity: f32,
-    bbox_size: f32,
-}
-
-// We use cells to allow each ball a unique ID
-thread_local!(static BALL_ID: Cell<f32> = Cell::new(2.0));
-
-impl PhysObject {
-    fn new_ball_id(pos: (f32, f32)) -> PhysObject {
-        BALL_ID.with(|thread_id| {
-            let id = thread_id.get();
-            thread_id.set(id + 1.0);
-            PhysObject {
-                tag: PhysType::Ball,
-                id,
-                hold: 0.0,
-                pos,
-                x_velocity: 0.0,
-                y_velocity: 0.0,
-                bbox_size: ROCK_BBOX
-            }
-        })
-    }
-
-    fn get_pos_p2(&self) -> Point2 {
-        Point2::new(self.pos.0, self.pos.1)
-    }
-}
-
-const PLAYER_BBOX: f32 = 24.0;
-const ROCK_BBOX: f32 = 24.0;
-
-/// *********************************************************************
-/// Now we have some constructor functions for different PhysObject.
-/// **********************************************************************
-
-fn create_player(spawn_pos: (f32, f32), player_id: f32) -> PhysObject {
-    PhysObject {
-        tag: PhysType::Player,
-        id: player_id,
-        hold: 0.0,
-        pos: spawn_pos,
-        x_velocity: 0.0,
-        y_velocity: 0.0,
-        bbox_size: PLAYER_BBOX
-    }
-}
-
-fn create_balls(balls_num: f32) -> Vec<PhysObject> {
-    let mut balls = Vec::new();
-    let distance = 100.0;
-    balls.append(&mut create_balls_collumn((balls_num / 2.0).ceil(), distance));
-    balls.append(&mut create_balls_collumn((balls_num / 2.0).floor(), distance + 72.0));
-
-    return balls;
-}
-
-fn create_balls_collumn(balls_num: f32, distance: f32) -> Vec<PhysObject> {
-    let space = 72.0;
-    let mut space_iter = -((balls_num - 1.0) * space) / 2.0;
-    let mut balls = Vec::new();
-    for _ in 0..balls_num as i32 {
-        balls.append(&mut create_ball_pair(distance, space_iter));
-        space_iter += space;
-    }
-
-    return balls;
-}
-
-fn create_ball_pair(x: f32, y: f32) -> Vec<PhysObject> {
-    let mut balls = Vec::new();
-    balls.push(PhysObject::new_ball_id((-x, y)));
-    balls.push(PhysObject::new_ball_id((x, y)));
-    
-    return balls;
-}
-
-fn reset_field(width: f32) -> (PhysObject, PhysObject, Vec<PhysObject>) {
-    let player1 = create_player((-3.0 * width / 8.0, 0.0), 1.0);
-    let player2 = create_player((3.0 * width / 8.0, 0.0), 2.0);
-    let balls = create_balls(6.0); 
-    return (player1, player2, balls);
-}
-
-fn ball_id_to_elem(balls: &Vec<PhysObject>, id: f32) -> Option<usize> {
-    for (index, ball) in balls.iter().enumerate() {
-        if ball.id == id {
-            return Some(index);
-        }
-    }
-    return None;
-}
-
-fn ball_follow(player: &PhysObject, balls: &mut Vec<PhysObject>, offset: f32) {
-    let index = ball_id_to_elem(&balls, player.hold);
-    match index {
-        Some(x) => {
-            balls[x].pos.0 = player.pos.0 + offset;
-            balls[x].pos.1 = player.pos.1;
-            balls[x].hold = player.id;
-        },
-        _ => ()
-    }
-}
-
-/// *********************************************************************
-/// Now we make functions to handle physics. We do simple Newtonian
-/// physics (so we do have inertia), and cap the max speed so that we
-/// don't have to worry too much about the insane levels of power a 
-/// player could reach.
-///
-/// Our unit of world space is simply pixels, though we do transform
-/// the coordinate system so that +y is up and -y is down.
-/// **********************************************************************
-
-/// Acceleration in pixels per second squared.
-const PLAYER_ACCELERATION: f32 = 8.0;
-/// Max velocity in pixels per second
-const MAX_PHYSICS_VEL: f32 = 200.0;
-/// Deacceleration in pixels per second squared.
-const BALL_DRAG: f32 = 20.0;
-
-fn player_handle_input(player: &mut PhysObject, input: &InputState, balls: &mut Vec<PhysObject>) {
-    player.x_velocity += PLAYER_ACCELERATION * (input.xaxis1pos + input.xaxis1neg);
-    player.y_velocity += PLAYER_ACCELERATION * (input.yaxis1pos + input.yaxis1neg);
-    if player.hold == 0.0 && input.holdball {
-        ball_pickup(player, balls);
-    } else if player.hold != 0.0 && !input.holdball {
-        ball_drop(player, balls);
-    }
-}
-
-fn ball_halt(ball: &mut PhysObject, dt: f32) {
-    if ball.x_velocity.abs().floor() != 0.0 || ball.y_velocity.abs().floor() != 0.0 {
-        let pythagoras = (ball.x_velocity.powf(2.0) + ball.y_velocity.powf(2.0)).powf(0.5);
-        ball.x_velocity -= BALL_DRAG * ball.x_velocity.signum() * dt * ball.x_velocity.abs() / pythagoras;
-        ball.y_velocity -= BALL_DRAG * ball.y_velocity.signum() * dt * ball.y_velocity.abs() / pythagoras;
-    }
-    else {
-        ball.x_velocity = 0.0;
-        ball.y_velocity = 0.0;
-        ball.hold = 0.0;
-    }
-}
-
-fn update_object_position(object: &mut PhysObject, width_lower: f32, width_upper: f32, height: f32, dt: f32) {
-    // Clamp the velocity to the max *efficiently*
-
-    if object.x_velocity.abs() > MAX_PHYSICS_VEL {
-        object.x_velocity = object.x_velocity.signum() * MAX_PHYSICS_VEL;
-    }
-    if object.y_velocity.abs() > MAX_PHYSICS_VEL {
-        object.y_velocity = object.y_velocity.signum() * MAX_PHYSICS_VEL;
-    }
-
-    let dxv = object.x_velocity * dt;
-    let dyv = object.y_velocity * dt;
-
-    if object.pos.0 + dxv < width_lower {
-        object.pos.0 = 2.0 * width_lower - (object.pos.0 + dxv);
-        object.x_velocity *= -1.0;
-    }
-    else if object.pos.0 + dxv > width_upper {
-        object.pos.0 = 2.0 * width_upper - (object.pos.0 + dxv);
-        object.x_velocity *= -1.0;
-    }
-    else {
-        object.pos.0 += dxv;
-    }
-
-    if object.pos.1 + dyv < height / -2.0 {
-        object.pos.1 = -height - (object.pos.1 + dyv);
-        object.y_velocity *= -1.0;
-    }
-    else if object.pos.1 + dyv > height / 2.0 {
-        object.pos.1 = height - (object.pos.1 + dyv);
-        object.y_velocity *= -1.0;
-    }
-    else {
-        object.pos.1 += dyv;
-    }
-}
-
-fn collision_check(player: &PhysObject, balls: &Vec<PhysObject>) -> Vec<f32> {
-    let mut coll_balls = Vec::new();
-    for ball in balls {
-        let pdistance1 = ball.get_pos_p2() - player.get_pos_p2();
-        if pdistance1.norm() < (player.bbox_size + ball.bbox_size) {
-            coll_balls.push(ball.id)
-        }
-    }
-    return coll_balls;
-}
-
-fn ball_pickup(player: &mut PhysObject, balls: &Vec<PhysObject>) {
-    if player.hold != 0.0 {
-        return; //already holding
-    }
-    let coll_balls = collision_check(player, balls);
-    if !coll_balls.is_empty() {
-        let ball = ball_id_to_elem(balls, coll_balls[0]).unwrap();
-        player.hold = balls[ball].id;
-    }
-}
-
-fn ball_drop(player: &mut PhysObject, balls: &mut Vec<PhysObject>) {
-    let ball = ball_id_to_elem(balls, player.hold);
-    match ball {
-        Some(x) => {
-            balls[x].x_velocity = player.x_velocity;
-            balls[x].y_velocity = player.y_velocity;
-        },
-        _ => ()
-    }
-    player.hold = 0.0;
-}
-
-fn collision_check_score(player: &PhysObject, balls: &Vec<PhysObject>, alligment: f32) -> bool {
-    for ball in balls {
-        let pdistance = ball.get_pos_p2() - player.get_pos_p2();
-        if pdistance.norm() < (player.bbox_size + ball.bbox_size) && ball.hold == alligment {
-            return true;
-        }
-    }
-    return false;
-}
+const RED: graphics::Color = graphics::Color::new(1.0, 0.3, 0.3, 1.0);
+const BLUE: graphics::Color = graphics::Color::new(0.3, 0.3, 1.0, 1.0);
 
 /// Translates the world coordinate system, which
 /// has Y pointing up and the origin at the center,
@@ -289,7 +53,6 @@ fn world_to_screen_coords(screen_width: f32, screen_height: f32, point: Point2)
 /// is our "asset management system".  All the file names and such are
 /// just hard-coded.
 /// **********************************************************************
-
 struct Assets {
     player_red_image: graphics::Image,
     player_blue_image: graphics::Image,
@@ -321,17 +84,21 @@ impl Assets {
     fn actor_image(&mut self, object: &PhysObject) -> &mut graphics::Image {
         match object.tag {
             PhysType::Player => {
-                match object.id {
-                    x if x == 1.0 => &mut self.player_red_image,
-                    x if x == 2.0 => &mut self.player_blue_image,
-                    _ => &mut self.ball_image,
+                if object.id == 1.0 {
+                    &mut self.player_red_image
+                } else if object.id == 2.0 {
+                    &mut self.player_blue_image
+                } else {
+                    &mut self.ball_image
                 }
             }
             PhysType::Ball => {
-                match object.hold {
-                    x if x == 1.0 => &mut self.ball_red_image,
-                    x if x == 2.0 => &mut self.ball_blue_image,
-                    _ => &mut self.ball_image,
+                if object.hold == 1.0 {
+                    &mut self.ball_red_image
+                } else if object.hold == 2.0 {
+                    &mut self.ball_blue_image
+                } else {
+                    &mut self.ball_image
                 }
             },
         }
@@ -339,110 +106,733 @@ impl Assets {
 }
 
 /// **********************************************************************
-/// The `InputState` is exactly what it sounds like, it just keeps track of
-/// the user's input state so that we turn keyboard events into something
-/// state-based and device-independent.
+/// The game moves through phases: the title screen, the menu, a
+/// countdown before every point, the point itself, the pause screen and
+/// the game-over screen. `update`, `draw` and the key handlers all do
+/// whatever the current phase calls for.
 /// **********************************************************************
-#[derive(Debug)]
-pub struct InputState {
-    pub xaxis1pos: f32,
-    pub xaxis1neg: f32,
-    pub yaxis1pos: f32,
-    pub yaxis1neg: f32,
-    pub holdball: bool,
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Title,
+    Menu { selected: usize },
+    /// Seconds left before the point starts.
+    Countdown(f32),
+    Playing,
+    /// Unpausing counts down again, so nobody is caught off guard.
+    Paused,
+    GameOver(Outcome),
+    /// Watching a recorded match.
+    Replay,
 }
 
-impl Default for InputState {
-    fn default() -> Self {
-        InputState {
-            xaxis1pos: 0.0,
-            xaxis1neg: 0.0,
-            yaxis1pos: 0.0,
-            yaxis1neg: 0.0,
-            holdball: false,
+/// Seconds of countdown before each point.
+const COUNTDOWN: f32 = 3.0;
+
+/// Seconds the throw key has to be held for a full charge.
+const CHARGE_TIME: f32 = 1.0;
+
+/// A throw a human charges by holding the throw key. Letting go of the
+/// key throws, aimed the way the player is moving.
+#[derive(Debug, Clone, Copy, Default)]
+struct ThrowCharge {
+    // How far the throw is charged, from 0 to 1, while the key is down
+    level: Option<f32>,
+    // The charge of a throw to make on the next tick
+    thrown: Option<f32>,
+}
+
+impl ThrowCharge {
+    fn press(&mut self) {
+        if self.level.is_none() {
+            self.level = Some(0.0);
+        }
+    }
+
+    fn release(&mut self) {
+        self.thrown = self.level.take();
+    }
+
+    /// The keyboard input for one tick, with the throw if there is one.
+    fn input(&mut self, mut keyboard: InputState) -> InputState {
+        if let Some(level) = &mut self.level {
+            *level = (*level + TICK / CHARGE_TIME).min(1.0);
+        }
+        if let Some(charge) = self.thrown.take() {
+            keyboard.throw = true;
+            keyboard.charge = charge;
+            keyboard.aim = [keyboard.xaxis1pos + keyboard.xaxis1neg, keyboard.yaxis1pos + keyboard.yaxis1neg];
+        }
+        keyboard
+    }
+}
+
+/// The match being played, recorded for `--record`.
+struct Recording {
+    // Where the first match goes, later matches get a number
+    path: PathBuf,
+    matches: u32,
+    replay: Replay,
+}
+
+impl Recording {
+    fn path(&self) -> PathBuf {
+        if self.matches <= 1 {
+            return self.path.clone();
         }
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match self.path.extension() {
+            Some(extension) => format!("{}-{}.{}", stem, self.matches, extension.to_string_lossy()),
+            None => format!("{}-{}", stem, self.matches),
+        };
+        self.path.with_file_name(file_name)
     }
 }
 
+/// A replay being watched: how fast it plays and whether it is paused.
+struct ReplayViewer {
+    player: ReplayPlayer,
+    speed: f32,
+    paused: bool,
+    // Ticks owed at the current speed, so speeds below 1x work too
+    owed: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MenuItem {
+    Player(Side),
+    Play,
+    Quit,
+}
+
+const MENU_ITEMS: [MenuItem; 4] = [MenuItem::Player(Side::Left), MenuItem::Player(Side::Right), MenuItem::Play, MenuItem::Quit];
+
+/// A script as offered in the menu: the library if it compiled and
+/// passed the tests, or why not.
+type ScriptEntry = (String, Result<ScriptLibrary, ScriptError>);
+
 /// **********************************************************************
 /// Now we're getting into the actual game loop.  The `MainState` is our
 /// game's "global" state, it keeps track of everything we need for
-/// actually running the game.
+/// actually running the game. The game itself is in the `Simulation`,
+/// `MainState` only adds the window, the keyboard and the AI scripts.
 /// **********************************************************************
-
-    /// Mainstate
-
 struct MainState {
-    game: GameState,
+    phase: Phase,
+    sim: Simulation,
     assets: Assets,
-    source_player1: Option<String>,
-    source_player2: Option<String>,
-}
-
-struct GameState {
-    player1: PhysObject,
-    player2: PhysObject,
-    balls: Vec<PhysObject>,
-    score1: i32,
-    score2: i32,
-    screen_width: f32,
-    screen_height: f32,
     input1: InputState,
     input2: InputState,
+    charge1: ThrowCharge,
+    charge2: ThrowCharge,
+    controller1: Controller,
+    controller2: Controller,
+    script_settings: ScriptSettings,
+    // Every script there is, for the menu
+    scripts: Vec<ScriptEntry>,
+    // Who plays each side as picked in the menu: 0 is the keyboard,
+    // anything else the script before it in `scripts`
+    choices: [usize; 2],
+    // Rebuilds changed scripts while playing, not while watching a replay
+    watcher: Option<ScriptWatcher>,
+    recording: Option<Recording>,
+    replay: Option<ReplayViewer>,
+    // Shown for a few seconds after a script was reloaded
+    reload_note: Option<(String, Instant)>,
+    // The ladder file, unless matches are unrated
+    ladder: Option<PathBuf>,
+    // The scripts playing, as they were when the match started
+    rated: Option<[RatedScript; 2]>,
+}
+
+/// Who controls one of the players: the keyboard, or a script if there is one.
+#[derive(Default)]
+struct Controller {
+    script: Option<ScriptPlayer>,
+    // The script assigned to this player, even if it failed
+    script_name: Option<String>,
+    // Shown on screen: why a player that should have been a script is
+    // played by hand, or why its script forfeited the match
+    status: Option<String>,
+    // A rebuilt script waiting for the next point reset
+    pending: Option<ScriptLibrary>,
+    // The script failed during this match, which loses it
+    forfeited: bool,
+}
+
+impl Controller {
+    fn new(script: Option<ScriptPlayer>, script_name: Option<String>, status: Option<String>) -> Controller {
+        Controller {
+            script,
+            script_name,
+            status,
+            pending: None,
+            forfeited: false,
+        }
+    }
+
+    /// The script's move, or `keyboard` if there is no working script.
+    fn generate_input(&mut self, game: &GameState, p1: bool, keyboard: InputState) -> InputState {
+        let script = match self.script.as_mut() {
+            Some(script) => script,
+            None => return keyboard,
+        };
+        match script.generate_input(game, p1) {
+            Ok(input) => input,
+            Err(reason) => {
+                self.status = Some(script_failed(script, &reason, p1));
+                self.script = None;
+                self.forfeited = true;
+                keyboard
+            }
+        }
+    }
+
+    /// Tells the script about a point, dropping it if that fails.
+    fn point_scored(&mut self, game: &GameState, p1: bool, scorer: Side) {
+        if let Some(script) = self.script.as_mut() {
+            if let Err(reason) = script.point_scored(game, p1, scorer) {
+                self.status = Some(script_failed(script, &reason, p1));
+                self.script = None;
+                self.forfeited = true;
+            }
+        }
+    }
+
+    fn end_match(&mut self) {
+        if let Some(script) = self.script.as_mut() {
+            script.end_match();
+        }
+    }
+
+    /// Gets ready for a new match. A script that failed in the last one
+    /// is gone, and so is the line about it.
+    fn new_match(&mut self) {
+        self.end_match();
+        if self.forfeited {
+            self.status = None;
+            self.forfeited = false;
+        }
+    }
+
+    /// Takes a rebuilt script if it is the one this player uses.
+    fn offer(&mut self, event: &ReloadEvent) -> bool {
+        if self.script_name.as_deref() != Some(event.name.as_str()) {
+            return false;
+        }
+        if let Ok(library) = &event.result {
+            self.pending = Some(library.clone());
+        }
+        true
+    }
+
+    /// Swaps in the rebuilt script, if there is one waiting.
+    fn apply_pending(&mut self, settings: &ScriptSettings) -> Option<String> {
+        let library = self.pending.take()?;
+        match settings.load(&library) {
+            Ok(script) => {
+                println!("Script {} reloaded", library.name);
+                self.script = Some(script);
+                self.status = None;
+                Some(format!("Reloaded {}", library.name))
+            }
+            Err(reason) => {
+                eprintln!("Script {} could not be reloaded: {}", library.name, reason);
+                Some(format!("Reload of {} failed: {}", library.name, reason))
+            }
+        }
+    }
 }
 
 impl MainState {
-    fn new(ctx: &mut Context, source_player1: Option<String>, source_player2: Option<String>) -> GameResult<MainState> {
+    fn new(
+        ctx: &mut Context,
+        options: &Options,
+        scripts: Vec<ScriptEntry>,
+        players: [&PlayerChoice; 2],
+        phase: Phase,
+    ) -> GameResult<MainState> {
         println!("Game resource path: {:?}", ctx.filesystem);
 
         print_instructions();
-        
+
         let (width, height) = graphics::drawable_size(ctx);
 
+        let choice = |player: &PlayerChoice| {
+            let name = player.script_name();
+            scripts.iter().position(|(script_name, _)| Some(script_name.as_str()) == name).map_or(0, |index| index + 1)
+        };
+        let choices = [choice(players[0]), choice(players[1])];
+        let controller1 = load_controller(players[0], &scripts, &options.scripts, "P1");
+        let controller2 = load_controller(players[1], &scripts, &options.scripts, "P2");
+
+        println!("Seed: {}", options.game.seed);
         let assets = Assets::new(ctx)?;
-        let (player1, player2, balls) = reset_field(width);
-        let g = GameState {
-            player1,
-            player2,
-            balls,
-            score1: 0,
-            score2: 0,
-            screen_width: width,
-            screen_height: height,
+        let s = MainState {
+            phase,
+            sim: Simulation::with_config(GameConfig { width, height, ..options.game }),
+            assets,
             input1: InputState::default(),
             input2: InputState::default(),
-        };
-        let s = MainState {
-            game: g,
-            assets: assets,
-            source_player1: source_player1,
-            source_player2: source_player2,
+            charge1: ThrowCharge::default(),
+            charge2: ThrowCharge::default(),
+            controller1,
+            controller2,
+            script_settings: options.scripts,
+            scripts,
+            choices,
+            watcher: None,
+            recording: None,
+            replay: None,
+            reload_note: None,
+            ladder: if options.rated { Some(options.ladder.clone()) } else { None },
+            rated: None,
         };
 
         Ok(s)
     }
 
-    /* fn check_for_level_respawn(&mut self) {
-        if self.game.score1 >= 3 || self.game.score2 >= 3 {
-            // Reset game
+    /// Looks for rebuilt scripts. They are only swapped in at the next
+    /// point reset, so nothing changes in the middle of a point. Each one
+    /// is loaded once first, so one that builds but can't be played is
+    /// never swapped in and shows why in the menu.
+    fn check_for_reloads(&mut self) {
+        let events = match &self.watcher {
+            Some(watcher) => watcher.poll(),
+            None => return,
+        };
+        let settings = self.script_settings;
+        for mut event in events {
+            event.result = event.result.and_then(|library| settings.load(&library).map(|_| library));
+            if let Some(entry) = self.scripts.iter_mut().find(|(name, _)| *name == event.name) {
+                entry.1 = event.result.clone();
+            }
+            let wanted = self.controller1.offer(&event) | self.controller2.offer(&event);
+            match (&event.result, wanted) {
+                (Ok(_), true) => self.note(format!("{} rebuilt, swapping in at the next point", event.name)),
+                (Err(reason), true) => {
+                    eprintln!("Script {} could not be rebuilt: {}", event.name, reason);
+                    self.note(format!("Rebuilding {} failed: {}", event.name, reason));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn apply_reloads(&mut self) {
+        let settings = self.script_settings;
+        for note in [self.controller1.apply_pending(&settings), self.controller2.apply_pending(&settings)].iter().flatten() {
+            self.note(note.clone());
+        }
+    }
+
+    /// The name of a player as shown to the user.
+    fn player_name(&self, side: Side) -> String {
+        let (controller, colour) = match side {
+            Side::Left => (&self.controller1, "Red"),
+            Side::Right => (&self.controller2, "Blue"),
+        };
+        let name = match (&self.replay, side) {
+            (Some(viewer), Side::Left) => Some(viewer.player.replay.player1.as_str()),
+            (Some(viewer), Side::Right) => Some(viewer.player.replay.player2.as_str()),
+            (None, _) => controller.script.as_ref().map(|script| script.name()),
+        };
+        match name {
+            Some(name) if name != HUMAN_PLAYER => format!("{} ({})", colour, name),
+            _ => colour.to_string(),
+        }
+    }
+
+    fn result_line(&self) -> String {
+        let game = &self.sim.game;
+        let forfeit = if self.controller1.forfeited || self.controller2.forfeited { " (forfeit)" } else { "" };
+        match self.match_outcome() {
+            Some(Outcome::Winner(side)) => format!("{} wins {} - {}{}", self.player_name(side), game.score1, game.score2, forfeit),
+            _ => format!("Draw {} - {}{}", game.score1, game.score2, forfeit),
+        }
+    }
+
+    /// How the match ended, if it did. A script that failed forfeits it.
+    fn match_outcome(&self) -> Option<Outcome> {
+        match (self.controller1.forfeited, self.controller2.forfeited) {
+            (true, true) => Some(Outcome::Draw),
+            (true, false) => Some(Outcome::Winner(Side::Right)),
+            (false, true) => Some(Outcome::Winner(Side::Left)),
+            (false, false) => self.sim.game.outcome(),
+        }
+    }
+
+    /// Plays one tick of the match.
+    fn play_tick(&mut self) {
+        // Update the player state based on the user input.
+        let keyboard1 = self.charge1.input(self.input1);
+        let keyboard2 = self.charge2.input(self.input2);
+        let input1 = self.controller1.generate_input(&self.sim.game, true, keyboard1);
+        let input2 = self.controller2.generate_input(&self.sim.game, false, keyboard2);
+        if let Some(recording) = &mut self.recording {
+            recording.replay.record(input1, input2);
+        }
+
+        if let Some(scorer) = self.sim.tick(input1, input2) {
+            self.controller1.point_scored(&self.sim.game, true, scorer);
+            self.controller2.point_scored(&self.sim.game, false, scorer);
+            self.apply_reloads();
+            self.phase = Phase::Countdown(COUNTDOWN);
+        }
+        if let Some(outcome) = self.match_outcome() {
+            println!("Game over: {}", self.result_line());
+            self.save_recording();
+            self.controller1.end_match();
+            self.controller2.end_match();
+            if let (Some(path), Some([player1, player2])) = (&self.ladder, self.rated.take()) {
+                rate_matches(path, &[(player1, player2, outcome)]);
+            }
+            self.phase = Phase::GameOver(outcome);
+        }
+    }
+
+    /// Starts a new match with the same players and settings.
+    fn start_match(&mut self) {
+        println!("Seed: {}", self.sim.game.config.seed);
+        //A match left from the pause screen never got its end
+        self.controller1.new_match();
+        self.controller2.new_match();
+        //Scripts rebuilt since the last point play the new match, and are rated as such
+        self.apply_reloads();
+        self.sim = Simulation::with_config(self.sim.game.config);
+        self.input1 = InputState::default();
+        self.input2 = InputState::default();
+        self.charge1 = ThrowCharge::default();
+        self.charge2 = ThrowCharge::default();
+        self.phase = Phase::Countdown(COUNTDOWN);
+        self.start_recording();
+        self.rate_players();
+    }
+
+    /// Remembers who plays, so the match can be rated when it is over.
+    /// Only matches between two scripts are rated.
+    fn rate_players(&mut self) {
+        let rated = |controller: &Controller| controller.script.as_ref().map(|script| RatedScript::new(script.library()));
+        self.rated = match (rated(&self.controller1), rated(&self.controller2)) {
+            (Some(player1), Some(player2)) => Some([player1, player2]),
+            _ => None,
+        };
+    }
+
+    fn start_recording(&mut self) {
+        let name = |controller: &Controller| controller.script.as_ref().map_or(HUMAN_PLAYER, |script| script.name()).to_string();
+        let replay = Replay::new(self.sim.game.config, &name(&self.controller1), &name(&self.controller2));
+        if let Some(recording) = &mut self.recording {
+            recording.matches += 1;
+            recording.replay = replay;
+        }
+    }
+
+    /// Saves the match played so far, if it is being recorded.
+    fn save_recording(&self) {
+        if let Some(recording) = &self.recording {
+            if recording.replay.ticks() == 0 {
+                return;
+            }
+            let path = recording.path();
+            match recording.replay.save(&path) {
+                Ok(()) => println!("Replay saved to {}", path.display()),
+                Err(e) => eprintln!("Could not save the replay {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Plays the replay on at its speed.
+    fn replay_tick(&mut self) {
+        if let Some(viewer) = &mut self.replay {
+            if viewer.paused || viewer.player.finished() {
+                return;
+            }
+            viewer.owed += viewer.speed;
+            while viewer.owed >= 1.0 {
+                viewer.player.step();
+                viewer.owed -= 1.0;
+            }
+            self.sim.game = viewer.player.sim().game.clone();
+        }
+    }
+
+    fn replay_key(&mut self, ctx: &mut Context, keycode: KeyCode, keymod: KeyMods) {
+        let viewer = match &mut self.replay {
+            Some(viewer) => viewer,
+            None => return,
+        };
+        // Shift steps a second at a time
+        let step = if keymod.contains(KeyMods::SHIFT) { 60 } else { 1 };
+        match keycode {
+            KeyCode::Space => viewer.paused = !viewer.paused,
+            KeyCode::Right => {
+                viewer.paused = true;
+                let tick = viewer.player.tick() + step;
+                viewer.player.seek(tick);
+            }
+            KeyCode::Left => {
+                viewer.paused = true;
+                let tick = viewer.player.tick().saturating_sub(step);
+                viewer.player.seek(tick);
+            }
+            KeyCode::Up => viewer.speed = (viewer.speed * 2.0).min(16.0),
+            KeyCode::Down => viewer.speed = (viewer.speed / 2.0).max(0.125),
+            KeyCode::Home => viewer.player.seek(0),
+            KeyCode::Escape => event::quit(ctx),
+            _ => (),
+        }
+        self.sim.game = viewer.player.sim().game.clone();
+    }
+
+    fn draw_replay_hud(&self, ctx: &mut Context) -> GameResult {
+        let viewer = match &self.replay {
+            Some(viewer) => viewer,
+            None => return Ok(()),
+        };
+        if viewer.player.finished() {
+            self.draw_shade(ctx)?;
+            let lines = [
+                ("End of replay".to_string(), 32.0, graphics::WHITE),
+                (self.result_line(), 24.0, graphics::WHITE),
+            ];
+            self.draw_centered(ctx, &lines, self.sim.game.screen_height / 2.0 - 40.0)?;
+        }
+        let state = if viewer.paused { "paused" } else { "playing" };
+        let status = format!(
+            "Replay: tick {}/{} ({:.1} s), {}x, {}",
+            viewer.player.tick(),
+            viewer.player.len(),
+            viewer.player.tick() as f32 * TICK,
+            viewer.speed,
+            state,
+        );
+        let help = "Space: pause   Left/right: step (shift: 1 s)   Up/down: speed   Home: restart   Escape: quit";
+        let lines = [(status, 16.0, graphics::WHITE), (help.to_string(), 12.0, graphics::WHITE)];
+        self.draw_centered(ctx, &lines, self.sim.game.screen_height - 44.0)
+    }
+
+    /// The player picked in the menu for a side.
+    fn choice(&self, side: Side) -> PlayerChoice {
+        let choice = match side {
+            Side::Left => self.choices[0],
+            Side::Right => self.choices[1],
+        };
+        match choice {
+            0 => PlayerChoice::Human,
+            index => PlayerChoice::Script(self.scripts[index - 1].0.clone()),
+        }
+    }
+
+    /// Steps through the keyboard and the scripts for one side.
+    fn cycle_choice(&mut self, side: Side, forward: bool) {
+        let count = self.scripts.len() + 1;
+        let choice = match side {
+            Side::Left => &mut self.choices[0],
+            Side::Right => &mut self.choices[1],
+        };
+        *choice = if forward { (*choice + 1) % count } else { (*choice + count - 1) % count };
+    }
+
+    /// Loads the players picked in the menu and starts a match.
+    fn start_menu_match(&mut self) {
+        self.print_script_stats();
+        self.controller1 = load_controller(&self.choice(Side::Left), &self.scripts, &self.script_settings, "P1");
+        self.controller2 = load_controller(&self.choice(Side::Right), &self.scripts, &self.script_settings, "P2");
+        self.start_match();
+    }
+
+    fn menu_key(&mut self, ctx: &mut Context, keycode: KeyCode, selected: usize) {
+        match (keycode, MENU_ITEMS[selected]) {
+            (KeyCode::Up, _) | (KeyCode::W, _) => {
+                self.phase = Phase::Menu { selected: (selected + MENU_ITEMS.len() - 1) % MENU_ITEMS.len() };
+            }
+            (KeyCode::Down, _) | (KeyCode::S, _) => {
+                self.phase = Phase::Menu { selected: (selected + 1) % MENU_ITEMS.len() };
+            }
+            (KeyCode::Left, MenuItem::Player(side)) | (KeyCode::A, MenuItem::Player(side)) => self.cycle_choice(side, false),
+            (KeyCode::Right, MenuItem::Player(side))
+            | (KeyCode::D, MenuItem::Player(side))
+            | (KeyCode::Return, MenuItem::Player(side))
+            | (KeyCode::Space, MenuItem::Player(side)) => self.cycle_choice(side, true),
+            (KeyCode::Return, MenuItem::Play) | (KeyCode::Space, MenuItem::Play) => self.start_menu_match(),
+            (KeyCode::Return, MenuItem::Quit) | (KeyCode::Space, MenuItem::Quit) | (KeyCode::Escape, _) => event::quit(ctx),
+            _ => (),
+        }
+    }
+
+    fn draw_field(&mut self, ctx: &mut Context) -> GameResult {
+        // Loop over all objects drawing them...
+        {
+            let assets = &mut self.assets;
+            let game = &self.sim.game;
+            let coords = (game.screen_width, game.screen_height);
+
+            let p1 = &game.player1;
+            draw_physobject(assets, ctx, p1, coords)?;
+            let p2 = &game.player2;
+            draw_physobject(assets, ctx, p2, coords)?;
+
+            for b in &game.balls {
+                draw_physobject(assets, ctx, b, coords)?;
+            }
+
+            for (player, charge, colour) in [(p1, self.charge1, RED), (p2, self.charge2, BLUE)].iter() {
+                if let Some(level) = charge.level {
+                    draw_charge_meter(ctx, player, level, *colour, coords)?;
+                }
+            }
+        }
+
+        // And draw the GUI elements in the right places.
+        let score1_dest = Point2::new(10.0, 10.0);
+        let score2_dest = Point2::new(self.sim.game.screen_width - 160.0, 10.0);
+
+        let score1_str = format!("Score: {}", self.sim.game.score1);
+        let score2_str = format!("Score: {}", self.sim.game.score2);
+
+        let score1_display = graphics::Text::new((score1_str, self.assets.font, 48.0));
+        let score2_display = graphics::Text::new((score2_str, self.assets.font, 48.0));
+        graphics::draw(ctx, &score1_display, (score1_dest, 0.0, RED))?;
+        graphics::draw(ctx, &score2_display, (score2_dest, 0.0, BLUE))?;
+
+        if let WinCondition::TimeLimit(seconds) = self.sim.game.config.win_condition {
+            let left = (seconds - self.sim.game.time).max(0.0).ceil() as u32;
+            let clock_str = format!("{}:{:02}", left / 60, left % 60);
+            let clock_display = graphics::Text::new((clock_str, self.assets.font, 32.0));
+            let clock_dest = Point2::new(self.sim.game.screen_width / 2.0 - 40.0, 16.0);
+            graphics::draw(ctx, &clock_display, (clock_dest, 0.0, graphics::WHITE))?;
+        }
+        Ok(())
+    }
+
+    /// Darkens everything drawn so far, for text on top of the field.
+    fn draw_shade(&self, ctx: &mut Context) -> GameResult {
+        let shade = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, self.sim.game.screen_width, self.sim.game.screen_height),
+            graphics::Color::new(0.0, 0.0, 0.0, 0.6),
+        )?;
+        graphics::draw(ctx, &shade, graphics::DrawParam::new())
+    }
+
+    /// Draws lines of text centered on the screen, starting at `y`.
+    fn draw_centered(&self, ctx: &mut Context, lines: &[(String, f32, graphics::Color)], mut y: f32) -> GameResult {
+        for (line, size, colour) in lines {
+            let text = graphics::Text::new((line.as_str(), self.assets.font, *size));
+            let x = (self.sim.game.screen_width - text.width(ctx) as f32) / 2.0;
+            graphics::draw(ctx, &text, (Point2::new(x, y), 0.0, *colour))?;
+            y += size * 1.5;
         }
-    } */
+        Ok(())
+    }
+
+    fn draw_title(&self, ctx: &mut Context) -> GameResult {
+        let lines = [
+            ("Space Dodgeball".to_string(), 48.0, graphics::WHITE),
+            ("Press enter".to_string(), 24.0, graphics::WHITE),
+            (String::new(), 16.0, graphics::WHITE),
+            ("Red: WASD to move, space to pick up, hold left shift to throw".to_string(), 16.0, RED),
+            ("Blue: arrow keys to move, enter to pick up, hold right shift to throw".to_string(), 16.0, BLUE),
+        ];
+        self.draw_centered(ctx, &lines, self.sim.game.screen_height / 3.0)
+    }
+
+    fn draw_menu(&self, ctx: &mut Context, selected: usize) -> GameResult {
+        const HIGHLIGHT: graphics::Color = graphics::Color::new(1.0, 1.0, 0.3, 1.0);
+        let mut lines = vec![("Space Dodgeball".to_string(), 48.0, graphics::WHITE)];
+        for (index, item) in MENU_ITEMS.iter().enumerate() {
+            let (label, status) = match item {
+                MenuItem::Player(side) => {
+                    let colour = if *side == Side::Left { "Red" } else { "Blue" };
+                    let (name, status) = match self.choice(*side) {
+                        PlayerChoice::Human => (HUMAN_PLAYER.to_string(), ("keyboard".to_string(), graphics::WHITE)),
+                        PlayerChoice::Script(name) => {
+                            let status = match &self.scripts.iter().find(|(script_name, _)| *script_name == name).unwrap().1 {
+                                Ok(_) => ("ready".to_string(), graphics::Color::new(0.3, 1.0, 0.3, 1.0)),
+                                Err(reason) => (shorten(&reason.to_string(), 60), RED),
+                            };
+                            (name, status)
+                        }
+                    };
+                    (format!("{}: < {} >", colour, name), Some(status))
+                }
+                MenuItem::Play => ("Play".to_string(), None),
+                MenuItem::Quit => ("Quit".to_string(), None),
+            };
+            let colour = if index == selected { HIGHLIGHT } else { graphics::WHITE };
+            lines.push((label, 32.0, colour));
+            if let Some((status, colour)) = status {
+                lines.push((status, 16.0, colour));
+            }
+        }
+        lines.push(("Up/down to choose, left/right to change, enter to start".to_string(), 16.0, graphics::WHITE));
+        self.draw_centered(ctx, &lines, self.sim.game.screen_height / 8.0)
+    }
+
+    fn draw_game_over(&self, ctx: &mut Context, outcome: Outcome) -> GameResult {
+        let (headline, colour) = match outcome {
+            Outcome::Winner(Side::Left) => (format!("{} wins!", self.player_name(Side::Left)), RED),
+            Outcome::Winner(Side::Right) => (format!("{} wins!", self.player_name(Side::Right)), BLUE),
+            Outcome::Draw => ("Draw!".to_string(), graphics::WHITE),
+        };
+        let score = format!("{} - {}", self.sim.game.score1, self.sim.game.score2);
+        let lines = [
+            (headline, 48.0, colour),
+            (score, 32.0, graphics::WHITE),
+            ("R: rematch    Escape: menu".to_string(), 16.0, graphics::WHITE),
+        ];
+        self.draw_centered(ctx, &lines, self.sim.game.screen_height / 2.0 - 60.0)
+    }
+
+    fn note(&mut self, note: String) {
+        self.reload_note = Some((note, Instant::now()));
+    }
+
+    fn print_script_stats(&self) {
+        let scripts = [(&self.controller1.script, "P1"), (&self.controller2.script, "P2")];
+        for (script, player) in scripts.iter() {
+            if let Some(script) = script {
+                println!("{} script {}: {}", player, script.name(), script.stats());
+            }
+        }
+    }
+
 }
 
 /// **********************************************************************
 /// A couple of utility functions.
 /// **********************************************************************
-
 fn print_instructions() {
     println!();
     println!("Welcome to ASTROBLASTO 2: Electric Bogaloo!");
     println!();
     println!("How to play:");
-    println!("Player 1: WASD to move your ship, space bar to pick up and release balls");
-    println!("Player 2: arrow keys to move your ship, enter to pick up and release balls");
+    println!("Player 1: WASD to move your ship, space bar to pick up and release balls,");
+    println!("          hold left shift to charge a throw and let go to throw where you are heading");
+    println!("Player 2: arrow keys to move your ship, enter to pick up and release balls,");
+    println!("          hold right shift to charge a throw and let go to throw where you are heading");
     println!();
 }
 
+/// Cuts `text` down to `max` characters for the screen.
+fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(max - 3).collect();
+    short.push_str("...");
+    short
+}
+
+/// Logs a script failure and returns the line shown on screen.
+fn script_failed(script: &ScriptPlayer, reason: &ScriptError, p1: bool) -> String {
+    eprintln!("Script {} failed and forfeits the match: {}", script.name(), reason);
+    eprintln!("Script {} stats: {}", script.name(), script.stats());
+    let player = if p1 { "P1" } else { "P2" };
+    format!("{} script {} failed and forfeits, a rematch is played from the keyboard: {}", player, script.name(), reason)
+}
+
 fn draw_physobject(
     assets: &mut Assets,
     ctx: &mut Context,
@@ -450,7 +840,7 @@ fn draw_physobject(
     world_coords: (f32, f32),
 ) -> GameResult {
     let (screen_w, screen_h) = world_coords;
-    let pos = world_to_screen_coords(screen_w, screen_h, object.get_pos_p2());
+    let pos = world_to_screen_coords(screen_w, screen_h, Point2::new(object.pos.0, object.pos.1));
     let image = assets.actor_image(object);
     let drawparams = graphics::DrawParam::new()
         .dest(pos)
@@ -465,58 +855,21 @@ fn draw_physobject(
 /// **********************************************************************
 impl EventHandler for MainState {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
+        // One fixed `TICK` of the simulation per update, whatever the frame rate
         const DESIRED_FPS: u32 = 60;
 
         while timer::check_update_time(ctx, DESIRED_FPS) {
-            let seconds = 1.0 / (DESIRED_FPS as f32);
-
-            // Update the player state based on the user input.
-            match self.source_player1.as_ref() {
-                Some(scriptname1) => {
-                    self.game.input1 = ai_generate_input(&(self.game), &scriptname1, true);
-                },
-                None => ()
-            }
-            match self.source_player2.as_ref() {
-                Some(scriptname2) => {
-                    self.game.input2 = ai_generate_input(&(self.game), &scriptname2, false);
-                },
-                None => ()
-            }
-            player_handle_input(&mut self.game.player1, &self.game.input1, &mut self.game.balls);
-            player_handle_input(&mut self.game.player2, &self.game.input2, &mut self.game.balls);
-
-            // Update the physics for all PhysObjects.
-            // First the players...
-            update_object_position(&mut self.game.player1, -self.game.screen_width as f32 / 2.0, 0.0, self.game.screen_height as f32, seconds);
-            update_object_position(&mut self.game.player2, 0.0, self.game.screen_width as f32 / 2.0, self.game.screen_height as f32, seconds);
-            // Then the balls!
-            for ball in &mut self.game.balls {
-                update_object_position(ball, -self.game.screen_width as f32 / 2.0, self.game.screen_width as f32 / 2.0, self.game.screen_height as f32, seconds);
-                ball_halt(ball, seconds)
-            }
-
-            ball_follow(&self.game.player1, &mut self.game.balls, 32.0);
-            ball_follow(&self.game.player2, &mut self.game.balls, -32.0);
-
-            //self.check_for_level_respawn();
-
-            let (width, _) = graphics::drawable_size(ctx);
-            if collision_check_score(&self.game.player1, &self.game.balls, 2.0) {
-                self.game.score2 += 1;
-                let (fresh_player1, fresh_player2, fresh_balls) = reset_field(width);
-                self.game.player1 = fresh_player1;
-                self.game.player2 = fresh_player2;
-                self.game.balls = fresh_balls;
-            }
-            if collision_check_score(&self.game.player2, &self.game.balls, 1.0) {
-                self.game.score1 += 1;
-                let (fresh_player1, fresh_player2, fresh_balls) = reset_field(width);
-                self.game.player1 = fresh_player1;
-                self.game.player2 = fresh_player2;
-                self.game.balls = fresh_balls;
+            let seconds = TICK;
+
+            match self.phase {
+                Phase::Countdown(left) if left > seconds => self.phase = Phase::Countdown(left - seconds),
+                Phase::Countdown(_) => self.phase = Phase::Playing,
+                Phase::Playing => self.play_tick(),
+                Phase::Replay => self.replay_tick(),
+                _ => (), // Nothing moves
             }
         }
+        self.check_for_reloads();
 
         Ok(())
     }
@@ -526,32 +879,49 @@ impl EventHandler for MainState {
         // Just clear the screen...
         graphics::clear(ctx, graphics::Color::new(0.2, 0.2, 0.2, 1.0));
 
-        // Loop over all objects drawing them...
-        {
-            let assets = &mut self.assets;
-            let coords = (self.game.screen_width, self.game.screen_height);
-
-            let p1 = &self.game.player1;
-            draw_physobject(assets, ctx, p1, coords)?;
-            let p2 = &self.game.player2;
-            draw_physobject(assets, ctx, p2, coords)?;
-
-            for b in &self.game.balls {
-                draw_physobject(assets, ctx, b, coords)?;
+        match self.phase {
+            Phase::Title => self.draw_title(ctx)?,
+            Phase::Menu { selected } => self.draw_menu(ctx, selected)?,
+            Phase::Countdown(left) => {
+                self.draw_field(ctx)?;
+                let count = (left.ceil() as u32).to_string();
+                self.draw_centered(ctx, &[(count, 96.0, graphics::WHITE)], self.sim.game.screen_height / 2.0 - 60.0)?;
+            }
+            Phase::Playing => self.draw_field(ctx)?,
+            Phase::Paused => {
+                self.draw_field(ctx)?;
+                self.draw_shade(ctx)?;
+                let lines = [
+                    ("Paused".to_string(), 48.0, graphics::WHITE),
+                    ("Escape: resume    Q: quit to menu".to_string(), 16.0, graphics::WHITE),
+                ];
+                self.draw_centered(ctx, &lines, self.sim.game.screen_height / 2.0 - 40.0)?;
+            }
+            Phase::GameOver(outcome) => {
+                self.draw_field(ctx)?;
+                self.draw_shade(ctx)?;
+                self.draw_game_over(ctx, outcome)?;
+            }
+            Phase::Replay => {
+                self.draw_field(ctx)?;
+                self.draw_replay_hud(ctx)?;
             }
         }
 
-        // And draw the GUI elements in the right places.
-        let score1_dest = Point2::new(10.0, 10.0);
-        let score2_dest = Point2::new(480.0, 10.0);
-
-        let score1_str = format!("Score: {}", self.game.score1);
-        let score2_str = format!("Score: {}", self.game.score2);
-
-        let score1_display = graphics::Text::new((score1_str, self.assets.font, 48.0));
-        let score2_display = graphics::Text::new((score2_str, self.assets.font, 48.0));
-        graphics::draw(ctx, &score1_display, (score1_dest, 0.0, graphics::Color::new(1.0, 0.3, 0.3, 1.0)))?;
-        graphics::draw(ctx, &score2_display, (score2_dest, 0.0, graphics::Color::new(0.3, 0.3, 1.0, 1.0)))?;
+        // Scripts that failed, or never loaded and left their player to the keyboard
+        let status_lines = [&self.controller1.status, &self.controller2.status];
+        let mut status_dest = Point2::new(10.0, self.sim.game.screen_height - 30.0);
+        for status in status_lines.iter().copied().flatten() {
+            let status_display = graphics::Text::new((status.as_str(), self.assets.font, 16.0));
+            graphics::draw(ctx, &status_display, (status_dest, 0.0, graphics::WHITE))?;
+            status_dest.y -= 20.0;
+        }
+        if let Some((note, since)) = &self.reload_note {
+            if since.elapsed() < Duration::from_secs(3) {
+                let note_display = graphics::Text::new((note.as_str(), self.assets.font, 16.0));
+                graphics::draw(ctx, &note_display, (status_dest, 0.0, graphics::Color::new(0.3, 1.0, 0.3, 1.0)))?;
+            }
+        }
 
         // Then we flip the screen...
         graphics::present(ctx)?;
@@ -574,258 +944,539 @@ impl EventHandler for MainState {
         &mut self,
         ctx: &mut Context,
         keycode: KeyCode,
-        _keymod: KeyMods,
+        keymod: KeyMods,
         _repeat: bool,
     ) {
-        match keycode {
-            KeyCode::P => {
-                let img = graphics::screenshot(ctx).expect("Could not take screenshot");
-                img.encode(ctx, graphics::ImageFormat::Png, "/screenshot.png")
-                .expect("Could not save screenshot");
+        if keycode == KeyCode::P {
+            let img = graphics::screenshot(ctx).expect("Could not take screenshot");
+            img.encode(ctx, graphics::ImageFormat::Png, "/screenshot.png")
+            .expect("Could not save screenshot");
+        }
+        // The players only move while a point is on, or about to be
+        let in_play = matches!(self.phase, Phase::Countdown(_) | Phase::Playing);
+        match self.phase {
+            Phase::Title => match keycode {
+                KeyCode::Return | KeyCode::Space => self.phase = Phase::Menu { selected: 0 },
+                KeyCode::Escape => event::quit(ctx),
+                _ => (),
+            },
+            Phase::Menu { selected } => self.menu_key(ctx, keycode, selected),
+            Phase::Countdown(_) | Phase::Playing => {
+                if let KeyCode::Escape | KeyCode::Pause = keycode {
+                    self.phase = Phase::Paused;
+                }
             }
-            KeyCode::Escape => event::quit(ctx),
-            _ => (), // Do nothing
+            Phase::Paused => match keycode {
+                KeyCode::Escape | KeyCode::Pause => self.phase = Phase::Countdown(COUNTDOWN),
+                KeyCode::Q => {
+                    self.save_recording();
+                    self.phase = Phase::Menu { selected: 0 };
+                }
+                _ => (),
+            },
+            Phase::GameOver(_) => match keycode {
+                KeyCode::R => self.start_match(),
+                KeyCode::Escape => self.phase = Phase::Menu { selected: 0 },
+                _ => (),
+            },
+            Phase::Replay => self.replay_key(ctx, keycode, keymod),
+        }
+        if !in_play {
+            return;
         }
-        if self.source_player1.is_none() {
+        if self.controller1.script.is_none() {
             match keycode {
                 KeyCode::W => {
-                    self.game.input1.yaxis1pos = 1.0;
+                    self.input1.yaxis1pos = 1.0;
                 }
                 KeyCode::S => {
-                    self.game.input1.yaxis1neg = -1.0;
+                    self.input1.yaxis1neg = -1.0;
                 }
                 KeyCode::D => {
-                    self.game.input1.xaxis1pos = 1.0;
+                    self.input1.xaxis1pos = 1.0;
                 }
                 KeyCode::A => {
-                    self.game.input1.xaxis1neg = -1.0;
+                    self.input1.xaxis1neg = -1.0;
                 }
                 KeyCode::Space => {
-                    self.game.input1.holdball = true;
+                    self.input1.holdball = true;
                 }
+                KeyCode::LShift => self.charge1.press(),
                 _ => (), // Do nothing
             }
         }
-        if self.source_player2.is_none() {
+        if self.controller2.script.is_none() {
             match keycode {
                 KeyCode::Up => {
-                    self.game.input2.yaxis1pos = 1.0;
+                    self.input2.yaxis1pos = 1.0;
                 }
                 KeyCode::Down => {
-                    self.game.input2.yaxis1neg = -1.0;
+                    self.input2.yaxis1neg = -1.0;
                 }
                 KeyCode::Right => {
-                    self.game.input2.xaxis1pos = 1.0;
+                    self.input2.xaxis1pos = 1.0;
                 }
                 KeyCode::Left => {
-                    self.game.input2.xaxis1neg = -1.0;
+                    self.input2.xaxis1neg = -1.0;
                 }
                 KeyCode::Return => {
-                    self.game.input2.holdball = true;
+                    self.input2.holdball = true;
                 }
+                KeyCode::RShift => self.charge2.press(),
                 _ => (), // Do nothing
             }
         }
     }
 
     fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods) {
-        if self.source_player1.is_none() {
+        if self.controller1.script.is_none() {
             match keycode {
                 KeyCode::W => {
-                    self.game.input1.yaxis1pos = 0.0;
+                    self.input1.yaxis1pos = 0.0;
                 }
                 KeyCode::S => {
-                    self.game.input1.yaxis1neg = 0.0;
+                    self.input1.yaxis1neg = 0.0;
                 }
                 KeyCode::D => {
-                    self.game.input1.xaxis1pos = 0.0;
+                    self.input1.xaxis1pos = 0.0;
                 }
                 KeyCode::A => {
-                    self.game.input1.xaxis1neg = 0.0;
+                    self.input1.xaxis1neg = 0.0;
                 }
                 KeyCode::Space => {
-                    self.game.input1.holdball = false;
+                    self.input1.holdball = false;
                 }
+                KeyCode::LShift => self.charge1.release(),
                 _ => (), // Do nothing
             }
         }
-        if self.source_player2.is_none() {
+        if self.controller2.script.is_none() {
             match keycode {
                 KeyCode::Up => {
-                    self.game.input2.yaxis1pos = 0.0;
+                    self.input2.yaxis1pos = 0.0;
                 }
                 KeyCode::Down => {
-                    self.game.input2.yaxis1neg = 0.0;
+                    self.input2.yaxis1neg = 0.0;
                 }
                 KeyCode::Right => {
-                    self.game.input2.xaxis1pos = 0.0;
+                    self.input2.xaxis1pos = 0.0;
                 }
                 KeyCode::Left => {
-                    self.game.input2.xaxis1neg = 0.0;
+                    self.input2.xaxis1neg = 0.0;
                 }
                 KeyCode::Return => {
-                    self.game.input2.holdball = false;
+                    self.input2.holdball = false;
                 }
+                KeyCode::RShift => self.charge2.release(),
                 _ => (), // Do nothing
             }
         }
     }
 }
 
-//AI-scripting functions
-
-fn test_plugin(a: isize, b: isize, name: &str) -> isize {
-    if Library::new(name).is_err() {
-        eprintln!("Error during add test: {:?}", Library::new(name).err());
-        panic!();
-    }
-
-    let lib = Library::new(name).unwrap();
-    unsafe {
-        let func: Symbol<AddFunc> = lib.get(b"add").unwrap();
-        let answer = func(a, b);
-        answer
-        
+/// A bar above a player that fills up while they charge a throw.
+fn draw_charge_meter(
+    ctx: &mut Context,
+    player: &PhysObject,
+    level: f32,
+    colour: graphics::Color,
+    world_coords: (f32, f32),
+) -> GameResult {
+    let (screen_w, screen_h) = world_coords;
+    let top = Point2::new(player.pos.0 - player.bbox_size, player.pos.1 + player.bbox_size + 12.0);
+    let pos = world_to_screen_coords(screen_w, screen_h, top);
+    let width = 2.0 * player.bbox_size;
+    let empty = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        graphics::Rect::new(pos.x, pos.y, width, 6.0),
+        graphics::Color::new(0.0, 0.0, 0.0, 0.6),
+    )?;
+    graphics::draw(ctx, &empty, graphics::DrawParam::new())?;
+    if level > 0.0 {
+        let full = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(pos.x, pos.y, width * level, 6.0),
+            colour,
+        )?;
+        graphics::draw(ctx, &full, graphics::DrawParam::new())?;
     }
+    Ok(())
 }
 
-fn ai_generate_input(state: &GameState, name: &str, p1: bool) -> InputState {
-    let lib = Library::new(name).unwrap();
-
-    unsafe {
-        let func: Symbol<AIFunc> = lib.get(b"calculate_move").unwrap();
-        func(state, p1)
+/// **********************************************************************
+/// Helpers for the subcommands of `main`.
+/// **********************************************************************
+fn print_script_list(sources: Vec<(String, Result<ScriptSource, ScriptError>)>) {
+    println!("Players to choose from:");
+    println!("  {} (keyboard)", HUMAN_PLAYER);
+    for (name, source) in sources {
+        match source {
+            Ok(source) => {
+                let manifest = &source.manifest;
+                let mut line = format!("  {}", name);
+                if !manifest.version.is_empty() {
+                    line += &format!(" {}", manifest.version);
+                }
+                if !manifest.author.is_empty() {
+                    line += &format!(" by {}", manifest.author);
+                }
+                println!("{} ({})", line, source.path.display());
+            }
+            Err(reason) => println!("  {} (broken: {})", name, reason),
+        }
     }
 }
 
-fn compile_file(path: &Path) {
-    let mut compile_file = Command::new("rustc");
-    compile_file.args(&["--crate-type", "cdylib", path.as_os_str().to_str().unwrap()]).status().expect("process failed to execute");
+/// Compiles the scripts that are needed; only scripts that changed since
+/// the last run are actually rebuilt.
+fn build_scripts(
+    sources: Vec<(String, Result<ScriptSource, ScriptError>)>,
+    wanted: &[&str],
+) -> Vec<ScriptEntry> {
+    eprintln!("Reading files from script folder:");
+    let mut build_cache = BuildCache::open();
+    let mut libraries = Vec::new();
+    for (script_name, source) in sources {
+        if !wanted.contains(&script_name.as_str()) {
+            continue;
+        }
+        //Compile the script, it is tested when it is loaded
+        let library = source.and_then(|source| source.build_cached(&mut build_cache));
+        match &library {
+            Ok(_) => eprintln!("Script {} OK", script_name),
+            //Not correct, script disqualified
+            Err(reason) => eprintln!("Script {} disqualified: {}", script_name, reason),
+        }
+        libraries.push((script_name, library));
+    }
+    if let Err(e) = build_cache.save() {
+        eprintln!("Could not save the script build cache: {}", e);
+    }
+    libraries
 }
 
-/// **********************************************************************
-/// Finally our main function!  Which merely sets up a config and calls
-/// `ggez::event::run()` with our `EventHandler` type. (Yeah right...)
-/// **********************************************************************
-
-pub fn main() -> GameResult {
-    let args: Vec<String> = env::args().collect();
-    //AI script loading
-    let paths = fs::read_dir("src/script/").unwrap();
-
-    //Name of selected script with functioning test function
-    // let mut maybe_name: Option<String> = None;
-    println!("Reading files from script folder:");
-
-    //Iterate over paths
-    let mut valid_scripts: Vec<String> = Vec::new();
-
-    for path_prewrap in paths {
-        let path = path_prewrap.unwrap().path();
-
-        println!("PreFilter: {}", path.file_name().unwrap().to_str().unwrap());
-
-        //Has to be
-        //* Not directory
-        //* Not the structs-file
-        //* Extension is .rs
-        if path.is_dir() {
-            continue
+/// Loads the script a player asked for. A script that was asked for but
+/// failed leaves the player to the keyboard, with the reason on screen.
+fn load_controller(
+    choice: &PlayerChoice,
+    libraries: &[ScriptEntry],
+    settings: &ScriptSettings,
+    player: &str,
+) -> Controller {
+    let name = match choice.script_name() {
+        Some(name) => name.to_string(),
+        None => {
+            eprintln!("No script loaded for {}", player);
+            return Controller::default();
         }
-        if path.file_name().unwrap() == "structs.rs" {
-            continue
+    };
+    //Loading runs the ABI version check and the add test
+    let library = libraries.iter().find(|(script_name, _)| *script_name == name).map(|(_, library)| library);
+    match library.cloned().unwrap_or_else(|| Err(ScriptError::Load("not built".to_string()))).and_then(|library| settings.load(&library)) {
+        Ok(script) => {
+            eprintln!("Script {} loaded for {}", name, player);
+            Controller::new(Some(script), Some(name), None)
         }
-        if path.extension().unwrap() != "rs" {
-            continue
+        Err(reason) => {
+            eprintln!("Script {} could not be used for {}: {}", name, player, reason);
+            let status = format!("{} script {} could not be loaded, keyboard control: {}", player, name, reason);
+            Controller::new(None, Some(name), Some(status))
         }
+    }
+}
 
-        //All pre-requirements met
-        //Compile and test the script
-        compile_file(path.as_path());
-        println!("FileName: {}", path.file_name().unwrap().to_str().unwrap());
-        println!("Name: {}", path.display());
+fn play(options: &Options, libraries: Vec<ScriptEntry>, player1: &PlayerChoice, player2: &PlayerChoice) -> GameResult {
+    let watcher = ScriptWatcher::start(options.script_dir.clone(), Duration::from_millis(500));
+    //Test every script once, so the menu can tell which ones work. They are
+    //played from copies, which leaves the originals free for the watcher to rebuild.
+    let scripts: Vec<ScriptEntry> = libraries.into_iter()
+        .map(|(name, library)| {
+            let tested = library
+                .and_then(|library| private_copy(&library))
+                .and_then(|library| options.scripts.load(&library).map(|_| library));
+            (name, tested)
+        })
+        .collect();
 
-        println!("Test upcoming:");
+    let cb = ContextBuilder::new("astroblasto", "ggez")
+        .window_setup(conf::WindowSetup::default().title("Astroblasto!"))
+        .window_mode(conf::WindowMode::default().dimensions(options.game.width, options.game.height))
+        .add_resource_path(resource_dir());
 
-        //Tests the code, 1 + 3 = 4. Mostly to check connectivity
+    let (ctx, events_loop) = &mut cb.build()?;
 
-        let full_path;
-        if cfg!(windows) {
-            full_path = format!("{}/{}", env::current_dir().unwrap().to_string_lossy(), path.file_name().unwrap().to_str().unwrap().replace("rs","dll"));
-        }
-        else if cfg!(unix) {
-            full_path = format!("{}/lib{}", env::current_dir().unwrap().to_string_lossy(), path.file_name().unwrap().to_str().unwrap().replace("rs","so"));
-        }
-        else {
-            eprintln!("What the fuck?!");
-            panic!();
+    //Straight into the match if the players were picked on the command line
+    let phase = if *player1 == PlayerChoice::Human && *player2 == PlayerChoice::Human {
+        Phase::Title
+    } else {
+        Phase::Countdown(COUNTDOWN)
+    };
+    let game = &mut MainState::new(ctx, options, scripts, [player1, player2], phase)?;
+    game.watcher = Some(watcher);
+    if let Some(path) = &options.record {
+        let replay = Replay::new(options.game, HUMAN_PLAYER, HUMAN_PLAYER);
+        game.recording = Some(Recording { path: path.clone(), matches: 0, replay });
+        //From the title screen the recording starts with the first match
+        if phase != Phase::Title {
+            game.start_recording();
         }
-        println!("wee");
-      
-        let answer = test_plugin(1,3, full_path.as_str());
-        println!("{}+{}:{}",1,3,answer);
+    }
+    if phase != Phase::Title {
+        game.rate_players();
+    }
+    let result = event::run(ctx, events_loop, game);
+    //A match that was left halfway is saved too
+    if matches!(game.phase, Phase::Countdown(_) | Phase::Playing | Phase::Paused) {
+        game.save_recording();
+    }
+    game.print_script_stats();
+    result
+}
 
-        if answer != 4 {
-            continue
-            //Not correct, script disqualified
+fn watch_replay(options: &Options, path: &Path) -> GameResult {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("Could not read the replay {}: {}", path.display(), e);
+            process::exit(2);
         }
+    };
+    println!("{} vs {}, {} ticks", replay.player1, replay.player2, replay.ticks());
+    let options = Options { game: replay.config, ..options.clone() };
 
-        println!("Test finished:");
+    let cb = ContextBuilder::new("astroblasto", "ggez")
+        .window_setup(conf::WindowSetup::default().title("Astroblasto! replay"))
+        .window_mode(conf::WindowMode::default().dimensions(replay.config.width, replay.config.height))
+        .add_resource_path(resource_dir());
 
-        //If it has not panicked by now, store the script file name
-        valid_scripts.push(full_path);
-        //maybe_name = Some(full_path);
-    }
+    let (ctx, events_loop) = &mut cb.build()?;
+
+    let game = &mut MainState::new(ctx, &options, Vec::new(), [&PlayerChoice::Human, &PlayerChoice::Human], Phase::Replay)?;
+    let player = ReplayPlayer::new(replay);
+    game.sim.game = player.sim().game.clone();
+    game.replay = Some(ReplayViewer { player, speed: 1.0, paused: false, owed: 0.0 });
+    event::run(ctx, events_loop, game)
+}
 
-    // We add the CARGO_MANIFEST_DIR/resources to the resource paths
-    // so that ggez will look in our cargo project directory for files.
-    let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+// We add the CARGO_MANIFEST_DIR/resources to the resource paths
+// so that ggez will look in our cargo project directory for files.
+fn resource_dir() -> PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
         let mut path = path::PathBuf::from(manifest_dir);
         path.push("resources");
         path
     } else {
         path::PathBuf::from("./resources")
-    };
+    }
+}
 
-    let cb = ContextBuilder::new("astroblasto", "ggez")
-        .window_setup(conf::WindowSetup::default().title("Astroblasto!"))
-        .window_mode(conf::WindowMode::default().dimensions(640.0, 480.0))
-        .add_resource_path(resource_dir);
+fn headless(options: &Options, libraries: &[ScriptEntry], player1: &PlayerChoice, player2: &PlayerChoice) {
+    let mut controller1 = load_controller(player1, libraries, &options.scripts, "P1");
+    let mut controller2 = load_controller(player2, libraries, &options.scripts, "P2");
+    let (result, replay) = headless::run_match(options.game, controller1.script.as_mut(), controller2.script.as_mut(), options.max_time);
+    if let (true, Some(script1), Some(script2)) = (options.rated, &controller1.script, &controller2.script) {
+        rate_matches(&options.ladder, &[(RatedScript::new(script1.library()), RatedScript::new(script2.library()), result.outcome)]);
+    }
+    if let Some(path) = &options.record {
+        match replay.save(path) {
+            Ok(()) => eprintln!("Replay saved to {}", path.display()),
+            Err(e) => eprintln!("Could not save the replay {}: {}", path.display(), e),
+        }
+    }
+    if options.json {
+        match serde_json::to_string_pretty(&result) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Could not write the result as JSON: {}", e),
+        }
+        return;
+    }
+    println!("{}", result);
+    let scripts = [(&controller1.script, "P1"), (&controller2.script, "P2")];
+    for (script, player) in scripts.iter() {
+        if let Some(script) = script {
+            println!("{} script {}: {}", player, script.name(), script.stats());
+        }
+    }
+}
 
-    let (ctx, events_loop) = &mut cb.build()?;
+fn tournament(options: &Options, libraries: Vec<ScriptEntry>) {
+    let scripts: Vec<ScriptLibrary> = libraries.into_iter().filter_map(|(_, library)| library.ok()).collect();
+    if scripts.len() < 2 {
+        eprintln!("A tournament needs at least two working scripts");
+        return;
+    }
+    let tournament = Tournament {
+        game: options.game,
+        seeds: options.seeds,
+        max_time: options.max_time,
+        scripts: options.scripts,
+        replay_dir: options.record.clone(),
+    };
+    let result = tournament.run(&scripts);
+    if options.rated {
+        let rated = |name: &str| scripts.iter().find(|script| script.name == name).map(RatedScript::new);
+        //A match a script couldn't even be loaded for says nothing about how strong it is
+        let matches: Vec<_> = result.matches.iter()
+            .filter(|record| record.result.played)
+            .filter_map(|record| Some((rated(&record.player1)?, rated(&record.player2)?, record.result.outcome)))
+            .collect();
+        rate_matches(&options.ladder, &matches);
+    }
+    println!();
+    print!("{}", result.standings_table());
+    if let Some(path) = &options.export {
+        match result.export(path) {
+            Ok(()) => println!("Results written to {}", path.display()),
+            Err(e) => eprintln!("Could not write the results to {}: {}", path.display(), e),
+        }
+    }
+}
 
-    let mut player1: Option<String> = None;
-    let mut player2: Option<String> = None;
+/// Updates the ladder with the outcomes of matches between scripts.
+fn rate_matches(path: &Path, matches: &[(RatedScript, RatedScript, Outcome)]) {
+    let mut ladder = match Ladder::open(path) {
+        Ok(ladder) => ladder,
+        Err(e) => {
+            eprintln!("Could not read the ladder {}, the matches are not rated: {}", path.display(), e);
+            return;
+        }
+    };
+    for (player1, player2, outcome) in matches {
+        ladder.record(player1, player2, *outcome);
+    }
+    if let Err(e) = ladder.save(path) {
+        eprintln!("Could not save the ladder {}: {}", path.display(), e);
+        return;
+    }
+    let mut shown = Vec::new();
+    for script in matches.iter().flat_map(|(player1, player2, _)| vec![player1, player2]) {
+        if shown.contains(&script) {
+            continue;
+        }
+        shown.push(script);
+        if let Some(entry) = ladder.entries.iter().find(|entry| entry.script == *script) {
+            eprintln!("Rating of {} {}: {:.0}", script.name, script.version(), entry.rating);
+        }
+    }
+}
 
-    for valid in valid_scripts {
-        //println!("currently checking: {} against {} and {}", valid, args.get(1).unwrap(), args.get(2).unwrap());
-        match args.get(1) {
-            Some(arg) => {
-                if valid.contains(arg) {
-                    player1 = Some(valid.clone());
-                }
-            },
-            None => ()
+fn show_ladder(options: &Options, script: Option<&str>) {
+    let ladder = match Ladder::open(&options.ladder) {
+        Ok(ladder) => ladder,
+        Err(e) => {
+            eprintln!("Could not read the ladder {}: {}", options.ladder.display(), e);
+            process::exit(2);
         }
-        match args.get(2) {
-            Some(arg) => {
-                if valid.contains(arg) {
-                    player2 = Some(valid.clone());
-                }
-            },
-            None => ()
+    };
+    match script {
+        None if ladder.entries.is_empty() => println!("No rated matches in {} yet", options.ladder.display()),
+        None => print!("{}", ladder.table()),
+        Some(name) => match ladder.history(name) {
+            Some(history) => print!("{}", history),
+            None => println!("{} has no rated matches in {}", name, options.ladder.display()),
+        },
+    }
+}
+
+/// **********************************************************************
+/// Finally our main function!  Which merely sets up a config and calls
+/// `ggez::event::run()` with our `EventHandler` type. (Yeah right...)
+/// **********************************************************************
+pub fn main() -> GameResult {
+    let args: Vec<String> = env::args().collect();
+
+    //Started by the game itself to run a script in its own process
+    if args.get(1).map(String::as_str) == Some(scripting::WORKER_FLAG) {
+        if let Some(library) = args.get(2) {
+            if let Err(e) = scripting::run_worker(library) {
+                eprintln!("Script worker failed: {}", e);
+            }
         }
+        return Ok(());
     }
-    
-    match player1.clone() {
-        Some(name) => println!("Script {} loaded for P1", name),
-        None => println!("No script loaded for P1"),
+
+    let options = match cli::parse(args.into_iter().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!();
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    match &options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        //A replay needs no scripts, only the recorded inputs
+        Command::Replay { path } => return watch_replay(&options, path),
+        Command::Ladder { script } => {
+            show_ladder(&options, script.as_deref());
+            return Ok(());
+        }
+        Command::Rules => {
+            print!("{}", rules::to_toml(&options.game.rules));
+            return Ok(());
+        }
+        _ => (),
     }
-    match player2.clone() {
-        Some(name) => println!("Script {} loaded for P2", name),
-        None => println!("No script loaded for P2"),
+
+    //AI script loading
+    let sources = match discover_scripts(&options.script_dir) {
+        Ok(sources) => sources,
+        Err(e) => {
+            eprintln!("Could not read the script folder {}: {}", options.script_dir.display(), e);
+            Vec::new()
+        }
+    };
+    let names: Vec<String> = sources.iter().map(|(name, _)| name.clone()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+    //The scripts each command needs, checked before anything is compiled
+    let (wanted, errors): (Vec<&str>, Vec<String>) = match &options.command {
+        //The menu offers every script
+        Command::Play { player1, player2 } => {
+            let errors = vec![player1.check(&names).err(), player2.check(&names).err()];
+            (names.clone(), errors.into_iter().flatten().collect())
+        }
+        Command::Headless { player1, player2 } => {
+            let errors = vec![player1.check(&names).err(), player2.check(&names).err()];
+            let wanted = vec![player1.script_name(), player2.script_name()];
+            (wanted.into_iter().flatten().collect(), errors.into_iter().flatten().collect())
+        }
+        Command::Tournament { scripts } if scripts.is_empty() => (names.clone(), Vec::new()),
+        Command::Tournament { scripts } => {
+            let choices: Vec<PlayerChoice> = scripts.iter().map(|name| PlayerChoice::Script(name.clone())).collect();
+            let errors = choices.iter().filter_map(|choice| choice.check(&names).err()).collect();
+            (names.iter().copied().filter(|name| scripts.iter().any(|script| script == name)).collect(), errors)
+        }
+        Command::ListScripts | Command::Ladder { .. } | Command::Replay { .. } | Command::Rules | Command::Help => (Vec::new(), Vec::new()),
+    };
+    if !errors.is_empty() || options.command == Command::ListScripts {
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        print_script_list(sources);
+        if !errors.is_empty() {
+            process::exit(2);
+        }
+        return Ok(());
     }
 
-    let game = &mut MainState::new(ctx, player1, player2)?;
-    event::run(ctx, events_loop, game)
-}
\ No newline at end of file
+    let libraries = build_scripts(sources, &wanted);
+    match &options.command {
+        Command::Play { player1, player2 } => play(&options, libraries, player1, player2),
+        Command::Headless { player1, player2 } => {
+            headless(&options, &libraries, player1, player2);
+            Ok(())
+        }
+        Command::Tournament { .. } => {
+            tournament(&options, libraries);
+            Ok(())
+        }
+        Command::ListScripts | Command::Ladder { .. } | Command::Replay { .. } | Command::Rules | Command::Help => Ok(()),
+    }
+}
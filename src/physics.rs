@@ -0,0 +1,314 @@
+//! How things move: Newtonian physics with inertia, friction and drag,
+//! walls, and collisions between round objects.
+//!
+//! Everything here works in seconds and is given the length of the step,
+//! so a game plays the same whether it is stepped 60 times per second or
+//! 240. How fast, how heavy and how bouncy things are is up to the
+//! `GameRules` of the match.
+//!
+//! Our unit of world space is simply pixels, though we do transform
+//! the coordinate system so that +y is up and -y is down.
+
+use std::cmp::Ordering;
+
+use crate::simulation::{GameRules, InputState, PhysObject, PhysType};
+
+fn speed(object: &PhysObject) -> f32 {
+    (object.x_velocity * object.x_velocity + object.y_velocity * object.y_velocity).sqrt()
+}
+
+/// Slows `object` down by `deceleration * dt` without turning it around.
+/// Returns whether that stopped it.
+fn slow_down(object: &mut PhysObject, deceleration: f32, dt: f32) -> bool {
+    let speed = speed(object);
+    if speed <= deceleration * dt {
+        object.x_velocity = 0.0;
+        object.y_velocity = 0.0;
+        return true;
+    }
+    let scale = 1.0 - deceleration * dt / speed;
+    object.x_velocity *= scale;
+    object.y_velocity *= scale;
+    false
+}
+
+/// Keeps `object` below `max_speed` without changing its direction.
+fn cap_speed(object: &mut PhysObject, max_speed: f32) {
+    let speed = speed(object);
+    if speed > max_speed {
+        object.x_velocity *= max_speed / speed;
+        object.y_velocity *= max_speed / speed;
+    }
+}
+
+/// One half of a stick, kept between 0 and `max` (1 or -1). Scripts can
+/// send anything, and a NaN would stick to the player for good.
+fn axis(value: f32, max: f32) -> f32 {
+    if value.is_finite() {
+        (value * max).clamp(0.0, 1.0) * max
+    } else {
+        0.0
+    }
+}
+
+/// Speeds a player up the way its stick points, for `dt` seconds.
+/// Friction works against it all the time, so a player left alone
+/// slowly comes to a stop.
+pub(crate) fn accelerate(player: &mut PhysObject, input: &InputState, rules: &GameRules, dt: f32) {
+    let x = axis(input.xaxis1pos, 1.0) + axis(input.xaxis1neg, -1.0);
+    let y = axis(input.yaxis1pos, 1.0) + axis(input.yaxis1neg, -1.0);
+    player.x_velocity += rules.player_acceleration * x * dt;
+    player.y_velocity += rules.player_acceleration * y * dt;
+    slow_down(player, rules.player_friction, dt);
+    cap_speed(player, rules.max_player_speed);
+}
+
+/// Drag on a ball. A ball that stops is nobody's anymore.
+pub(crate) fn ball_halt(ball: &mut PhysObject, rules: &GameRules, dt: f32) {
+    if slow_down(ball, rules.ball_drag, dt) {
+        ball.hold = 0.0;
+    }
+}
+
+/// The part of the field an object's center has to stay in.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Bounds {
+    pub(crate) left: f32,
+    pub(crate) right: f32,
+    pub(crate) bottom: f32,
+    pub(crate) top: f32,
+}
+
+impl Bounds {
+    /// Shrunk by `margin` on every side, as far as it can be.
+    pub(crate) fn inset(self, margin: f32) -> Bounds {
+        let x = margin.min((self.right - self.left) / 2.0);
+        let y = margin.min((self.top - self.bottom) / 2.0);
+        Bounds { left: self.left + x, right: self.right - x, bottom: self.bottom + y, top: self.top - y }
+    }
+}
+
+/// Where an object went during a step: where it started, where it
+/// bounced and where it ended up, with the time of each. In between it
+/// moved in a straight line.
+#[derive(Debug, Clone)]
+pub(crate) struct Path {
+    points: Vec<(f32, (f32, f32))>,
+}
+
+impl Path {
+    pub(crate) fn position(&self, time: f32) -> (f32, f32) {
+        for pair in self.points.windows(2) {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            if time <= t1 {
+                let s = if t1 > t0 { (time - t0) / (t1 - t0) } else { 1.0 };
+                return (p0.0 + (p1.0 - p0.0) * s, p0.1 + (p1.1 - p0.1) * s);
+            }
+        }
+        self.points[self.points.len() - 1].1
+    }
+
+    /// How fast it was moving at `time`, bounces and all.
+    fn velocity(&self, time: f32) -> (f32, f32) {
+        for pair in self.points.windows(2) {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            if time < t1 && t1 > t0 {
+                return ((p1.0 - p0.0) / (t1 - t0), (p1.1 - p0.1) / (t1 - t0));
+            }
+        }
+        (0.0, 0.0)
+    }
+}
+
+/// Walls are bounced off at most this often in one step; after that the
+/// object is simply kept on the field.
+const MAX_BOUNCES: usize = 8;
+
+/// Seconds until `pos`, moving at `velocity`, reaches `low` or `high`.
+fn time_to_wall(pos: f32, velocity: f32, low: f32, high: f32) -> f32 {
+    if velocity > 0.0 {
+        ((high - pos) / velocity).max(0.0)
+    } else if velocity < 0.0 {
+        ((low - pos) / velocity).max(0.0)
+    } else {
+        f32::INFINITY
+    }
+}
+
+/// Moves an object for `dt` seconds, bouncing off the walls of `bounds`
+/// at the exact moment it reaches them, however fast it goes.
+pub(crate) fn update_object_position(object: &mut PhysObject, bounds: Bounds, rules: &GameRules, dt: f32) -> Path {
+    // Balls may go faster than players, or there would be no throwing
+    match object.tag {
+        PhysType::Player => cap_speed(object, rules.max_player_speed),
+        PhysType::Ball => cap_speed(object, rules.max_throw_speed),
+    }
+
+    // Collisions may have pushed it out a little
+    object.pos.0 = object.pos.0.clamp(bounds.left, bounds.right);
+    object.pos.1 = object.pos.1.clamp(bounds.bottom, bounds.top);
+
+    let mut path = Path { points: vec![(0.0, object.pos)] };
+    let mut time = 0.0;
+    for _ in 0..MAX_BOUNCES {
+        let to_x = time_to_wall(object.pos.0, object.x_velocity, bounds.left, bounds.right);
+        let to_y = time_to_wall(object.pos.1, object.y_velocity, bounds.bottom, bounds.top);
+        let to_wall = to_x.min(to_y);
+        if time + to_wall >= dt {
+            break;
+        }
+        object.pos.0 += object.x_velocity * to_wall;
+        object.pos.1 += object.y_velocity * to_wall;
+        time += to_wall;
+        if to_x <= to_y {
+            object.x_velocity *= -1.0;
+        }
+        if to_y <= to_x {
+            object.y_velocity *= -1.0;
+        }
+        path.points.push((time, object.pos));
+    }
+    let left = dt - time;
+    object.pos.0 = (object.pos.0 + object.x_velocity * left).clamp(bounds.left, bounds.right);
+    object.pos.1 = (object.pos.1 + object.y_velocity * left).clamp(bounds.bottom, bounds.top);
+    path.points.push((dt, object.pos));
+    path
+}
+
+/// The first time two circles, moving along `a` and `b`, are within
+/// `reach` of each other: the swept version of a distance test, so a
+/// fast ball can't skip past a player between two steps.
+pub(crate) fn first_contact(a: &Path, b: &Path, reach: f32) -> Option<f32> {
+    let mut times: Vec<f32> = a.points.iter().chain(b.points.iter()).map(|(time, _)| *time).collect();
+    times.sort_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal));
+    times.dedup();
+    let offset = |time: f32| {
+        let (pa, pb) = (a.position(time), b.position(time));
+        (pb.0 - pa.0, pb.1 - pa.1)
+    };
+    for pair in times.windows(2) {
+        let (t0, t1) = (pair[0], pair[1]);
+        let (x0, y0) = offset(t0);
+        let (x1, y1) = offset(t1);
+        let c = x0 * x0 + y0 * y0 - reach * reach;
+        if c < 0.0 {
+            return Some(t0);
+        }
+        // |offset(t0) + s * d| = reach, for s from 0 to 1
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let a = dx * dx + dy * dy;
+        let b = 2.0 * (x0 * dx + y0 * dy);
+        let discriminant = b * b - 4.0 * a * c;
+        if a == 0.0 || discriminant < 0.0 {
+            continue;
+        }
+        let s = (-b - discriminant.sqrt()) / (2.0 * a);
+        if (0.0..=1.0).contains(&s) {
+            return Some(t0 + s * (t1 - t0));
+        }
+    }
+    None
+}
+
+/// Pushes two overlapping circles apart and, if they are moving towards
+/// each other, bounces them off each other. An object with an inverse
+/// mass of 0 is immovable. Returns whether they touched.
+fn collide(a: &mut PhysObject, inv_mass_a: f32, b: &mut PhysObject, inv_mass_b: f32, restitution: f32) -> bool {
+    let inv_mass = inv_mass_a + inv_mass_b;
+    let dx = b.pos.0 - a.pos.0;
+    let dy = b.pos.1 - a.pos.1;
+    let distance = (dx * dx + dy * dy).sqrt();
+    let touching = a.bbox_size + b.bbox_size;
+    if inv_mass == 0.0 || distance >= touching || distance == 0.0 {
+        return false;
+    }
+    let (nx, ny) = (dx / distance, dy / distance);
+
+    let overlap = touching - distance;
+    a.pos.0 -= nx * overlap * inv_mass_a / inv_mass;
+    a.pos.1 -= ny * overlap * inv_mass_a / inv_mass;
+    b.pos.0 += nx * overlap * inv_mass_b / inv_mass;
+    b.pos.1 += ny * overlap * inv_mass_b / inv_mass;
+
+    bounce(a, inv_mass_a, b, inv_mass_b, (nx, ny), restitution);
+    true
+}
+
+/// Bounces two touching objects off each other along `normal`, the unit
+/// vector from `a` to `b`, if they are moving towards each other.
+fn bounce(a: &mut PhysObject, inv_mass_a: f32, b: &mut PhysObject, inv_mass_b: f32, normal: (f32, f32), restitution: f32) {
+    let inv_mass = inv_mass_a + inv_mass_b;
+    let (nx, ny) = normal;
+    let closing = (b.x_velocity - a.x_velocity) * nx + (b.y_velocity - a.y_velocity) * ny;
+    if inv_mass != 0.0 && closing < 0.0 {
+        let impulse = -(1.0 + restitution) * closing / inv_mass;
+        a.x_velocity -= impulse * inv_mass_a * nx;
+        a.y_velocity -= impulse * inv_mass_a * ny;
+        b.x_velocity += impulse * inv_mass_b * nx;
+        b.y_velocity += impulse * inv_mass_b * ny;
+    }
+}
+
+/// A resting ball that a player ran through, or that went through a
+/// player, during the step is put back where they touched, relative to
+/// the player, and bounced off it there.
+pub(crate) fn stop_tunneling(player: &mut PhysObject, player_path: &Path, ball: &mut PhysObject, ball_path: &Path, rules: &GameRules) {
+    let reach = player.bbox_size + ball.bbox_size;
+    if ball.hold != 0.0 || ball.distance_to(player) < reach {
+        return;
+    }
+    // Touching from the start means they are already moving apart
+    let time = match first_contact(player_path, ball_path, reach) {
+        Some(time) if time > 0.0 => time,
+        _ => return,
+    };
+    let (player_x, player_y) = player_path.position(time);
+    let (ball_x, ball_y) = ball_path.position(time);
+    let (dx, dy) = (ball_x - player_x, ball_y - player_y);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 {
+        return;
+    }
+    let normal = (dx / distance, dy / distance);
+    ball.pos = (player.pos.0 + normal.0 * reach, player.pos.1 + normal.1 * reach);
+    // It may have bounced off a wall since, going the other way
+    let (x_velocity, y_velocity) = ball_path.velocity(time);
+    ball.x_velocity = x_velocity;
+    ball.y_velocity = y_velocity;
+    bounce(player, 1.0 / rules.player_mass, ball, 1.0 / rules.ball_mass, normal, rules.restitution);
+}
+
+/// A loose `ball` hit by a flying `thrown` one is now thrown by whoever
+/// threw that.
+fn knock_on(ball: &mut PhysObject, thrown: &PhysObject, held: [f32; 2]) {
+    if ball.hold == 0.0 && thrown.hold != 0.0 && !held.contains(&thrown.id) {
+        ball.hold = thrown.hold;
+    }
+}
+
+/// Balls bounce off each other, and resting balls off the players.
+/// A held ball doesn't give way: it moves with its player, so it can be
+/// used to block. A thrown ball is not bounced off the players: it
+/// either hits the opponent or passes its own thrower. A loose ball that
+/// a thrown one knocks is thrown too, by the same player, so nobody can
+/// hide behind the balls lying around.
+pub(crate) fn resolve_collisions(player1: &mut PhysObject, player2: &mut PhysObject, balls: &mut [PhysObject], rules: &GameRules) {
+    let held = [player1.hold, player2.hold];
+    let inv_mass = |ball: &PhysObject| if held.contains(&ball.id) { 0.0 } else { 1.0 / rules.ball_mass };
+    for i in 0..balls.len() {
+        let (head, tail) = balls.split_at_mut(i + 1);
+        let a = &mut head[i];
+        for b in tail {
+            let (inv_mass_a, inv_mass_b) = (inv_mass(a), inv_mass(b));
+            if collide(a, inv_mass_a, b, inv_mass_b, rules.restitution) {
+                knock_on(a, b, held);
+                knock_on(b, a, held);
+            }
+        }
+    }
+    for player in [player1, player2] {
+        for ball in balls.iter_mut().filter(|ball| ball.hold == 0.0) {
+            collide(player, 1.0 / rules.player_mass, ball, 1.0 / rules.ball_mass, rules.restitution);
+        }
+    }
+}
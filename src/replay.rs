@@ -0,0 +1,155 @@
+//! Recording matches and playing them back.
+//!
+//! The simulation is deterministic, so a replay is only the match's
+//! `GameConfig` (which holds the seed) and the inputs of both players for
+//! every tick. Feeding those inputs back through a fresh `Simulation`
+//! plays the match again exactly. Inputs are stored as runs, since
+//! players tend to hold the same keys for many ticks.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{GameConfig, InputState, Simulation};
+
+/// Bump this whenever the simulation changes in a way that makes old
+/// replays play out differently.
+pub const REPLAY_FORMAT: u32 = 7;
+
+/// The same inputs for `count` ticks in a row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputRun {
+    pub count: u32,
+    pub input1: InputState,
+    pub input2: InputState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub format: u32,
+    pub config: GameConfig,
+    /// Who played each side: a script name or `human`.
+    pub player1: String,
+    pub player2: String,
+    pub runs: Vec<InputRun>,
+}
+
+impl Replay {
+    pub fn new(config: GameConfig, player1: &str, player2: &str) -> Replay {
+        Replay {
+            format: REPLAY_FORMAT,
+            config,
+            player1: player1.to_string(),
+            player2: player2.to_string(),
+            runs: Vec::new(),
+        }
+    }
+
+    /// Adds the inputs of one tick.
+    pub fn record(&mut self, input1: InputState, input2: InputState) {
+        if let Some(run) = self.runs.last_mut() {
+            if run.input1 == input1 && run.input2 == input2 {
+                run.count += 1;
+                return;
+            }
+        }
+        self.runs.push(InputRun { count: 1, input1, input2 });
+    }
+
+    /// Number of ticks recorded.
+    pub fn ticks(&self) -> usize {
+        self.runs.iter().map(|run| run.count as usize).sum()
+    }
+
+    /// The inputs of every tick, in order.
+    pub fn inputs(&self) -> Vec<(InputState, InputState)> {
+        self.runs.iter()
+            .flat_map(|run| (0..run.count).map(move |_| (run.input1, run.input2)))
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let text = fs::read_to_string(path)?;
+        let replay: Replay = serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if replay.format != REPLAY_FORMAT {
+            let message = format!("replay format {} can't be played by this version, which plays format {}", replay.format, REPLAY_FORMAT);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok(replay)
+    }
+}
+
+/// Plays a replay back one tick at a time, forwards or backwards.
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    inputs: Vec<(InputState, InputState)>,
+    sim: Simulation,
+    tick: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> ReplayPlayer {
+        ReplayPlayer {
+            inputs: replay.inputs(),
+            sim: Simulation::with_config(replay.config),
+            replay,
+            tick: 0,
+        }
+    }
+
+    pub fn sim(&self) -> &Simulation {
+        &self.sim
+    }
+
+    /// Ticks played so far.
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.tick >= self.inputs.len()
+    }
+
+    /// Plays the next tick. Returns false at the end of the replay.
+    pub fn step(&mut self) -> bool {
+        match self.inputs.get(self.tick) {
+            Some(&(input1, input2)) => {
+                self.sim.tick(input1, input2);
+                self.tick += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Jumps to just after `tick`. The simulation can't run backwards,
+    /// so going back replays the match from the start.
+    pub fn seek(&mut self, tick: usize) {
+        let tick = tick.min(self.inputs.len());
+        if tick < self.tick {
+            self.sim = Simulation::with_config(self.replay.config);
+            self.tick = 0;
+        }
+        while self.tick < tick {
+            self.step();
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Rules files: the `GameRules` of a match as TOML.
+//!
+//! A rules file only needs the rules it changes, everything else keeps
+//! its default:
+//!
+//! ```toml
+//! balls_per_side = 8
+//! ball_drag = 40.0
+//! max_throw_speed = 600.0
+//! ```
+//!
+//! Single rules can be changed on the command line too, by name, which
+//! wins over the file.
+
+use std::fs;
+use std::path::Path;
+
+use crate::simulation::GameRules;
+
+/// Reads the rules in the TOML file at `path`.
+pub fn load(path: &Path) -> Result<GameRules, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read the rules {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("Invalid rules in {}: {}", path.display(), e))
+}
+
+/// The rules as a TOML file, every rule in it.
+pub fn to_toml(rules: &GameRules) -> String {
+    toml::to_string(rules).unwrap_or_default()
+}
+
+/// Changes the rule called `name` to `value`, written as in a rules file.
+pub fn set(rules: &mut GameRules, name: &str, value: &str) -> Result<(), String> {
+    let invalid = || format!("Invalid value for rule {}: {}", name, value);
+    let mut table = toml::Value::try_from(*rules).map_err(|e| e.to_string())?;
+    let parsed = toml::from_str::<toml::Value>(&format!("value = {}", value))
+        .ok()
+        .and_then(|parsed| parsed.get("value").cloned())
+        .ok_or_else(invalid)?;
+    match table.get_mut(name) {
+        Some(rule) => *rule = parsed,
+        None => return Err(format!("Unknown rule {}", name)),
+    }
+    *rules = table.try_into().map_err(|_| invalid())?;
+    Ok(())
+}
+
+/// Rules the game can't be played with, like balls without a size or
+/// more balls than fit on a `width` by `height` field.
+pub fn check(rules: &GameRules, width: f32, height: f32) -> Result<(), String> {
+    let positive = [
+        ("ball_spacing", rules.ball_spacing),
+        ("player_radius", rules.player_radius),
+        ("ball_radius", rules.ball_radius),
+        ("player_mass", rules.player_mass),
+        ("ball_mass", rules.ball_mass),
+        ("max_player_speed", rules.max_player_speed),
+        ("max_throw_speed", rules.max_throw_speed),
+    ];
+    let not_negative = [
+        ("hold_offset", rules.hold_offset),
+        ("pickup_reach", rules.pickup_reach),
+        ("player_acceleration", rules.player_acceleration),
+        ("player_friction", rules.player_friction),
+        ("ball_drag", rules.ball_drag),
+        ("min_throw_speed", rules.min_throw_speed),
+    ];
+    for (name, value) in positive {
+        if !(value > 0.0 && value.is_finite()) {
+            return Err(format!("Rule {} has to be more than 0, not {}", name, value));
+        }
+    }
+    for (name, value) in not_negative {
+        if !(value >= 0.0 && value.is_finite()) {
+            return Err(format!("Rule {} can't be negative, not {}", name, value));
+        }
+    }
+    if !(0.0..=1.0).contains(&rules.restitution) {
+        return Err(format!("Rule restitution has to be from 0 to 1, not {}", rules.restitution));
+    }
+    if rules.min_throw_speed > rules.max_throw_speed {
+        return Err(format!("Rule min_throw_speed ({}) is more than max_throw_speed ({})", rules.min_throw_speed, rules.max_throw_speed));
+    }
+    let fit = max_balls_per_side(rules, width, height);
+    if rules.balls_per_side > fit {
+        return Err(format!(
+            "Rule balls_per_side is {}, but only {} fit on a {} by {} field with ball_spacing {}",
+            rules.balls_per_side, fit, width, height, rules.ball_spacing
+        ));
+    }
+    Ok(())
+}
+
+/// How many balls fit on each side: two columns, 100 and 100 plus
+/// `ball_spacing` from the middle, one ball every `ball_spacing`. The
+/// first column takes the odd ball, so one ball needs only the first.
+fn max_balls_per_side(rules: &GameRules, width: f32, height: f32) -> u32 {
+    let edge = |distance: f32| distance + rules.ball_radius <= width / 2.0;
+    let diameter = 2.0 * rules.ball_radius;
+    if !edge(100.0) || diameter > height {
+        return 0;
+    }
+    let column = ((height - diameter) / rules.ball_spacing).floor() as u32 + 1;
+    if edge(100.0 + rules.ball_spacing) {
+        column.saturating_mul(2)
+    } else {
+        column.min(1)
+    }
+}
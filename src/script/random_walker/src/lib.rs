@@ -0,0 +1,81 @@
+/// **********************************************************************
+/// Example of a script built with cargo, so it can use crates.
+/// Wanders around its half at random and grabs any ball it runs into.
+///
+/// It is also an example of a stateful script: it keeps its own random
+/// generator, seeded from the match, and only picks a new direction
+/// every half second.
+/// **********************************************************************
+use std::ffi::c_void;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use script_abi::*;
+
+/// Ticks between changes of direction.
+const WANDER_TICKS: u32 = 30;
+
+struct Walker {
+    rng: StdRng,
+    direction: InputState,
+    ticks_left: u32,
+}
+
+impl Walker {
+    fn wander(&mut self) {
+        self.direction = InputState {
+            xaxis1pos: self.rng.gen_range(0.0, 1.0),
+            xaxis1neg: -self.rng.gen_range(0.0, 1.0),
+            yaxis1pos: self.rng.gen_range(0.0, 1.0),
+            yaxis1neg: -self.rng.gen_range(0.0, 1.0),
+            holdball: true,
+            ..InputState::default()
+        };
+        self.ticks_left = WANDER_TICKS;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn add(a: isize, b: isize) -> isize {
+    a + b
+}
+
+#[no_mangle]
+pub extern "C" fn script_abi_version() -> u32 {
+    SCRIPT_ABI_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn init(info: &MatchInfo) -> *mut c_void {
+    //Both sides get their own numbers from the same match seed
+    let seed = info.seed.wrapping_mul(2).wrapping_add(info.p1 as u64);
+    new_state(|| Walker {
+        rng: StdRng::seed_from_u64(seed),
+        direction: InputState::default(),
+        ticks_left: 0,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn calculate_move(state: *mut c_void, _context: &GameContext, out: &mut InputState) -> MoveStatus {
+    let walker: &mut Walker = unsafe { state_mut(state) };
+    catch_move(out, || {
+        if walker.ticks_left == 0 {
+            walker.wander();
+        }
+        walker.ticks_left -= 1;
+        walker.direction
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn on_point_scored(state: *mut c_void, _point: &PointScored) -> MoveStatus {
+    let walker: &mut Walker = unsafe { state_mut(state) };
+    //Off somewhere new after every point
+    catch_event(|| walker.ticks_left = 0)
+}
+
+#[no_mangle]
+pub extern "C" fn destroy(state: *mut c_void) {
+    unsafe { drop_state::<Walker>(state) }
+}
@@ -13,49 +13,54 @@ fn main() {
 		a + b
 	}
 	#[no_mangle]
-	pub extern "C" fn calculate_move(game: &GameState, p1: bool) -> InputState {
+	pub extern "C" fn script_abi_version() -> u32 {
+		SCRIPT_ABI_VERSION
+	}
+	#[no_mangle]
+	pub extern "C" fn calculate_move(context: &GameContext, out: &mut InputState) -> MoveStatus {
         //Code here
 
         //This function is called once per 'tick'
-        if p1 {
-            //script controlling left player
-            if game.player1.pos.0 < -10.0 {
-                InputState {
-                    xaxis1pos: 1.0,
-                    xaxis1neg: 0.0,
-                    yaxis1pos: 0.0,
-                    yaxis1neg: 0.0,
-                    holdball: true,
+        //The view is mirrored for the right player, so this works for both sides
+        //catch_move keeps a panic in here from crashing the game
+        catch_move(out, || {
+            let me = context.view.me;
+            let opponent = context.view.opponent;
+            if me.hold == 0.0 {
+                //Empty handed: run to the closest ball lying on my side
+                let distance = |ball: &&PhysObject| (ball.pos[0] - me.pos[0]).hypot(ball.pos[1] - me.pos[1]);
+                let closest = context.view.balls().iter()
+                    .filter(|ball| ball.hold == 0.0 && ball.pos[0] < 0.0)
+                    .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+                match closest {
+                    Some(ball) => InputState {
+                        xaxis1pos: if ball.pos[0] > me.pos[0] { 1.0 } else { 0.0 },
+                        xaxis1neg: if ball.pos[0] < me.pos[0] { -1.0 } else { 0.0 },
+                        yaxis1pos: if ball.pos[1] > me.pos[1] { 1.0 } else { 0.0 },
+                        yaxis1neg: if ball.pos[1] < me.pos[1] { -1.0 } else { 0.0 },
+                        holdball: true,
+                        ..InputState::default()
+                    },
+                    None => InputState::default(),
                 }
-            } else {
+            } else if me.pos[0] < -10.0 {
                 InputState {
                     xaxis1pos: 1.0,
-                    xaxis1neg: 0.0,
-                    yaxis1pos: 0.0,
-                    yaxis1neg: 0.0,
-                    holdball: false,
-                }
-            }
-        } else {
-            //right player
-            if game.player2.pos.0 > 10.0 {
-                InputState {
-                    xaxis1pos: 0.0,
-                    xaxis1neg: -1.0,
-                    yaxis1pos: 0.0,
-                    yaxis1neg: 0.0,
                     holdball: true,
+                    ..InputState::default()
                 }
             } else {
+                //At the middle line: throw as hard as possible at the opponent
                 InputState {
-                    xaxis1pos: 0.0,
-                    xaxis1neg: -1.0,
-                    yaxis1pos: 0.0,
-                    yaxis1neg: 0.0,
+                    xaxis1pos: 1.0,
                     holdball: false,
+                    throw: true,
+                    aim: [opponent.pos[0] - me.pos[0], opponent.pos[1] - me.pos[1]],
+                    charge: 1.0,
+                    ..InputState::default()
                 }
             }
-        }
+        })
 
     }
 }
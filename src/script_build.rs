@@ -0,0 +1,355 @@
+//! Finding and compiling AI scripts.
+//!
+//! A script in the script folder is either
+//! * a single `.rs` file, compiled with plain `rustc`, or
+//! * a small cargo project in its own folder with a `script.toml`
+//!   manifest, built with `cargo build --release` so it can use crates.
+//!
+//! Either way the library ends up in `SCRIPT_CACHE_DIR`. Builds are
+//! cached: a script is only rebuilt when its sources, the shared structs
+//! or the script ABI version changed since the last build.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scripting::{ScriptError, SCRIPT_ABI_VERSION};
+
+/// Where the game looks for scripts.
+pub const SCRIPT_DIR: &str = "src/script";
+/// Where compiled scripts go.
+pub const SCRIPT_CACHE_DIR: &str = "target/scripts";
+/// The manifest file that makes a folder in `SCRIPT_DIR` a cargo script.
+pub const MANIFEST_FILE: &str = "script.toml";
+/// The index of `SCRIPT_CACHE_DIR`, which source hash each library was built from.
+const CACHE_INDEX_FILE: &str = "cache.json";
+/// The files every script is built against, besides its own sources.
+const SHARED_SOURCES: [&str; 2] = ["src/script/structs.rs", "script_abi/src/lib.rs"];
+
+/// The names of the functions a script exports.
+/// Cargo scripts may rename them in their manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntrySymbols {
+    pub add: String,
+    pub abi_version: String,
+    pub calculate_move: String,
+    pub init: String,
+    pub on_point_scored: String,
+    pub destroy: String,
+}
+
+impl Default for EntrySymbols {
+    fn default() -> Self {
+        EntrySymbols {
+            add: "add".to_string(),
+            abi_version: "script_abi_version".to_string(),
+            calculate_move: "calculate_move".to_string(),
+            init: "init".to_string(),
+            on_point_scored: "on_point_scored".to_string(),
+            destroy: "destroy".to_string(),
+        }
+    }
+}
+
+/// The `script.toml` of a cargo script. Single-file scripts get one made
+/// up from their file name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptManifest {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub entry: EntrySymbols,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptKind {
+    SingleFile,
+    Cargo,
+}
+
+/// A script found in the script folder, not yet compiled.
+#[derive(Debug, Clone)]
+pub struct ScriptSource {
+    /// The `.rs` file, or the folder of a cargo script.
+    pub path: PathBuf,
+    pub kind: ScriptKind,
+    pub manifest: ScriptManifest,
+}
+
+/// A compiled script, ready to be loaded by a `ScriptPlayer`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptLibrary {
+    pub name: String,
+    pub path: PathBuf,
+    pub entry: EntrySymbols,
+    /// `source_hash` of the sources it was built from, in hex.
+    #[serde(default)]
+    pub source_hash: String,
+}
+
+impl ScriptSource {
+    /// Looks at one entry of the script folder. Returns `None` for anything
+    /// that isn't a script: the structs-file, other extensions and folders
+    /// without a manifest.
+    pub fn from_path(path: &Path) -> Option<Result<ScriptSource, ScriptError>> {
+        if path.is_dir() {
+            let manifest_path = path.join(MANIFEST_FILE);
+            if !manifest_path.is_file() {
+                return None;
+            }
+            return Some(read_manifest(&manifest_path).map(|manifest| ScriptSource {
+                path: path.to_path_buf(),
+                kind: ScriptKind::Cargo,
+                manifest,
+            }));
+        }
+
+        //Has to be
+        //* Not the structs-file
+        //* Extension is .rs
+        if path.file_name().unwrap_or_default() == "structs.rs" {
+            return None;
+        }
+        if path.extension().unwrap_or_default() != "rs" {
+            return None;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        Some(Ok(ScriptSource {
+            path: path.to_path_buf(),
+            kind: ScriptKind::SingleFile,
+            manifest: ScriptManifest {
+                name,
+                author: String::new(),
+                version: String::new(),
+                entry: EntrySymbols::default(),
+            },
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    /// Hash of everything the compiled script depends on: its own sources,
+    /// the shared structs and the script ABI version.
+    pub fn source_hash(&self) -> io::Result<u64> {
+        let mut hasher = Fnv1a::new();
+        hasher.write(&SCRIPT_ABI_VERSION.to_le_bytes());
+        for shared in SHARED_SOURCES.iter() {
+            // The game may run somewhere without its own sources
+            if let Ok(bytes) = fs::read(shared) {
+                hasher.write(&bytes);
+            }
+        }
+        match self.kind {
+            ScriptKind::SingleFile => hasher.write(&fs::read(&self.path)?),
+            ScriptKind::Cargo => {
+                let mut files = Vec::new();
+                collect_files(&self.path, &mut files)?;
+                files.sort();
+                for file in files {
+                    hasher.write(file.strip_prefix(&self.path).unwrap_or(&file).to_string_lossy().as_bytes());
+                    hasher.write(&fs::read(&file)?);
+                }
+            }
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Like `build`, but reuses the library from the last build if nothing
+    /// it depends on has changed.
+    pub fn build_cached(&self, cache: &mut BuildCache) -> Result<ScriptLibrary, ScriptError> {
+        let hash = self.source_hash()
+            .map_err(|e| ScriptError::Compile(format!("could not read sources: {}", e)))?;
+        if let Some(entry) = cache.entries.get(self.name()) {
+            if entry.hash == format!("{:016x}", hash) && entry.library.is_file() {
+                return Ok(ScriptLibrary {
+                    name: self.manifest.name.clone(),
+                    path: entry.library.clone(),
+                    entry: self.manifest.entry.clone(),
+                    source_hash: entry.hash.clone(),
+                });
+            }
+        }
+
+        let library = self.build()?;
+        cache.entries.insert(self.name().to_string(), CacheEntry {
+            hash: library.source_hash.clone(),
+            library: library.path.clone(),
+        });
+        Ok(library)
+    }
+
+    /// Compiles the script into `SCRIPT_CACHE_DIR`.
+    pub fn build(&self) -> Result<ScriptLibrary, ScriptError> {
+        fs::create_dir_all(SCRIPT_CACHE_DIR)
+            .map_err(|e| ScriptError::Compile(format!("could not create {}: {}", SCRIPT_CACHE_DIR, e)))?;
+        let path = match self.kind {
+            ScriptKind::SingleFile => compile_file(&self.path)?,
+            ScriptKind::Cargo => cargo_build(&self.path)?,
+        };
+        // Building a cargo script may write its Cargo.lock, so hash after
+        let source_hash = self.source_hash().map(|hash| format!("{:016x}", hash)).unwrap_or_default();
+        Ok(ScriptLibrary {
+            name: self.manifest.name.clone(),
+            path,
+            entry: self.manifest.entry.clone(),
+            source_hash,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// `source_hash` of the script when it was built, in hex.
+    hash: String,
+    library: PathBuf,
+}
+
+/// Which scripts in `SCRIPT_CACHE_DIR` are up to date.
+/// Kept in `SCRIPT_CACHE_DIR/cache.json` between runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildCache {
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+impl BuildCache {
+    /// Reads the cache index. A missing or broken index is an empty cache,
+    /// which just means everything gets rebuilt.
+    pub fn open() -> BuildCache {
+        fs::read_to_string(Path::new(SCRIPT_CACHE_DIR).join(CACHE_INDEX_FILE))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(SCRIPT_CACHE_DIR)?;
+        let text = serde_json::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(Path::new(SCRIPT_CACHE_DIR).join(CACHE_INDEX_FILE), text)
+    }
+}
+
+/// 64-bit FNV-1a. The hash has to stay the same between runs and Rust
+/// versions, which `std`'s `DefaultHasher` doesn't promise.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// All files in a cargo script's folder, skipping any `target` folder.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.file_name().unwrap_or_default() != "target" {
+                collect_files(&path, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Finds every script in `dir`, together with the name used to report it.
+/// Scripts whose manifest can't be read are returned as errors.
+pub fn discover_scripts(dir: &Path) -> io::Result<Vec<(String, Result<ScriptSource, ScriptError>)>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+
+    let mut scripts = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        if let Some(source) = ScriptSource::from_path(&path) {
+            let name = source.as_ref().map(|source| source.name().to_string()).unwrap_or(name);
+            scripts.push((name, source));
+        }
+    }
+    Ok(scripts)
+}
+
+fn read_manifest(path: &Path) -> Result<ScriptManifest, ScriptError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| ScriptError::Manifest(format!("could not read {}: {}", path.display(), e)))?;
+    toml::from_str(&text).map_err(|e| ScriptError::Manifest(format!("{}: {}", path.display(), e)))
+}
+
+fn library_file_name(crate_name: &str) -> String {
+    format!("{}{}{}", env::consts::DLL_PREFIX, crate_name, env::consts::DLL_SUFFIX)
+}
+
+/// Compiles a single-file script into a library with `rustc` and
+/// returns the path of the library.
+fn compile_file(source: &Path) -> Result<PathBuf, ScriptError> {
+    let status = Command::new("rustc")
+        .args(["--crate-type", "cdylib", "--edition", "2018", "--out-dir", SCRIPT_CACHE_DIR])
+        .arg(source)
+        .status()
+        .map_err(|e| ScriptError::Compile(format!("could not run rustc: {}", e)))?;
+    if !status.success() {
+        return Err(ScriptError::Compile(format!("rustc exited with {}", status)));
+    }
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(Path::new(SCRIPT_CACHE_DIR).join(library_file_name(&stem)))
+}
+
+/// Builds a cargo script with `cargo build --release` and returns the
+/// path of the library. All cargo scripts share one target directory so
+/// common dependencies are only compiled once.
+fn cargo_build(dir: &Path) -> Result<PathBuf, ScriptError> {
+    let cargo_toml = dir.join("Cargo.toml");
+    let target_dir = Path::new(SCRIPT_CACHE_DIR).join("cargo");
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--release", "--manifest-path"])
+        .arg(&cargo_toml)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .map_err(|e| ScriptError::Compile(format!("could not run cargo: {}", e)))?;
+    if !status.success() {
+        return Err(ScriptError::Compile(format!("cargo exited with {}", status)));
+    }
+    let crate_name = library_crate_name(&cargo_toml)?;
+    Ok(target_dir.join("release").join(library_file_name(&crate_name)))
+}
+
+/// The name rustc gives the library of a cargo project: `[lib] name`
+/// if set, otherwise the package name with dashes turned into underscores.
+fn library_crate_name(cargo_toml: &Path) -> Result<String, ScriptError> {
+    let text = fs::read_to_string(cargo_toml)
+        .map_err(|e| ScriptError::Manifest(format!("could not read {}: {}", cargo_toml.display(), e)))?;
+    let value: toml::Value = toml::from_str(&text)
+        .map_err(|e| ScriptError::Manifest(format!("{}: {}", cargo_toml.display(), e)))?;
+    let name = value.get("lib").and_then(|lib| lib.get("name"))
+        .or_else(|| value.get("package").and_then(|package| package.get("name")))
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| ScriptError::Manifest(format!("{} has no package name", cargo_toml.display())))?;
+    Ok(name.replace('-', "_"))
+}
@@ -0,0 +1,708 @@
+//! Glue between the game and AI scripts.
+//! Scripts never see our own `GameState`; they get a `#[repr(C)]`
+//! `GameContext` from the shared `script_abi` crate instead.
+
+use std::env;
+use std::ffi::c_void;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::ptr;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+
+use crate::script_build::ScriptLibrary;
+use crate::simulation::{GameState, InputState, PhysObject, PhysType, Side};
+
+pub use script_abi::{
+    AbiVersionFunc, AddFunc, CalculateMoveFunc, DestroyFunc, GameContext, GameSnapshot, InitFunc, MatchInfo, MoveStatus,
+    PointScored, PointScoredFunc, SideView, StatefulMoveFunc, SCRIPT_ABI_VERSION,
+};
+
+fn abi_object(object: &PhysObject) -> script_abi::PhysObject {
+    script_abi::PhysObject {
+        tag: match object.tag {
+            PhysType::Player => script_abi::PhysType::Player,
+            PhysType::Ball => script_abi::PhysType::Ball,
+        },
+        id: object.id,
+        hold: object.hold,
+        pos: [object.pos.0, object.pos.1],
+        x_velocity: object.x_velocity,
+        y_velocity: object.y_velocity,
+        bbox_size: object.bbox_size,
+    }
+}
+
+/// Builds the `GameContext` of the player `p1` says and hands it to `f`.
+/// The context borrows temporary ball buffers, so it only lives for the
+/// duration of the call.
+pub fn with_context<R>(game: &GameState, p1: bool, f: impl FnOnce(&GameContext) -> R) -> R {
+    let balls: Vec<script_abi::PhysObject> = game.balls.iter().map(abi_object).collect();
+    let snapshot = GameSnapshot {
+        player1: abi_object(&game.player1),
+        player2: abi_object(&game.player2),
+        balls: balls.as_ptr(),
+        balls_len: balls.len(),
+        score1: game.score1,
+        score2: game.score2,
+        screen_width: game.screen_width,
+        screen_height: game.screen_height,
+        input1: game.input1,
+        input2: game.input2,
+    };
+    //Player 2 sees the field mirrored, so it is on the left too
+    let mirrored: Vec<script_abi::PhysObject> = if p1 {
+        Vec::new()
+    } else {
+        balls.iter().map(script_abi::PhysObject::mirrored).collect()
+    };
+    let view = if p1 {
+        SideView {
+            me: snapshot.player1,
+            opponent: snapshot.player2,
+            balls: balls.as_ptr(),
+            balls_len: balls.len(),
+            my_score: game.score1,
+            opponent_score: game.score2,
+            my_input: game.input1,
+            opponent_input: game.input2,
+        }
+    } else {
+        SideView {
+            me: snapshot.player2.mirrored(),
+            opponent: snapshot.player1.mirrored(),
+            balls: mirrored.as_ptr(),
+            balls_len: mirrored.len(),
+            my_score: game.score2,
+            opponent_score: game.score1,
+            my_input: game.input2.mirrored(),
+            opponent_input: game.input1.mirrored(),
+        }
+    };
+    let context = GameContext {
+        abi_version: SCRIPT_ABI_VERSION,
+        p1,
+        view,
+        game: snapshot,
+        rules: game.config.rules,
+    };
+    f(&context)
+}
+
+/// Everything that can go wrong with a script, from compiling it to
+/// calling it mid-match. A script that fails is dropped and its player
+/// falls back to manual control.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ScriptError {
+    /// The `script.toml` or `Cargo.toml` of a cargo script is broken.
+    Manifest(String),
+    /// `rustc` or `cargo` could not be run or did not accept the script.
+    Compile(String),
+    /// The compiled library could not be opened.
+    Load(String),
+    /// The library lacks one of the functions every script must export.
+    MissingSymbol(String),
+    /// `add(1, 3)` did not return 4, so the library can't be trusted.
+    TestFailed(isize),
+    /// The script was built against another version of `script_abi`.
+    AbiMismatch { script: u32, game: u32 },
+    /// The script caught a panic in one of its functions and reported
+    /// it, or `init` gave no state. A panic that isn't caught by the script
+    /// itself aborts the process it runs in.
+    Panicked(String),
+    /// The worker process running the script died or stopped making sense.
+    Crashed(String),
+    /// The script went over its per-tick time budget too often.
+    OverBudget { overruns: u32 },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Manifest(reason) => write!(f, "bad manifest: {}", reason),
+            ScriptError::Compile(reason) => write!(f, "compilation failed: {}", reason),
+            ScriptError::Load(reason) => write!(f, "could not load library: {}", reason),
+            ScriptError::MissingSymbol(symbol) => write!(f, "missing function `{}`", symbol),
+            ScriptError::TestFailed(answer) => write!(f, "add test failed, 1+3 gave {}", answer),
+            ScriptError::AbiMismatch { script, game } => {
+                write!(f, "built for script ABI version {}, the game uses {}", script, game)
+            }
+            ScriptError::Panicked(message) => write!(f, "panicked: {}", message),
+            ScriptError::Crashed(reason) => write!(f, "crashed: {}", reason),
+            ScriptError::OverBudget { overruns } => write!(f, "went over its time budget {} times", overruns),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// What happens to a script that keeps going over its time budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverrunPenalty {
+    /// The late answer is thrown away and the player does nothing this tick.
+    NoInput,
+    /// The script is dropped from the match.
+    Forfeit,
+}
+
+/// How long a script may spend in `calculate_move` each tick.
+/// The first `allowed_overruns` overruns are only counted, after that
+/// every overrun gets the `penalty`.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeBudget {
+    pub per_call: Duration,
+    pub allowed_overruns: u32,
+    pub penalty: OverrunPenalty,
+    /// An out-of-process script that hasn't answered after this long is
+    /// killed. In-process scripts can't be interrupted, only measured.
+    pub hang_timeout: Duration,
+}
+
+impl Default for TimeBudget {
+    fn default() -> Self {
+        TimeBudget {
+            per_call: Duration::from_millis(2),
+            allowed_overruns: 10,
+            penalty: OverrunPenalty::NoInput,
+            hang_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// How scripts are run: in which process and on what budget.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScriptSettings {
+    pub budget: TimeBudget,
+    pub out_of_process: bool,
+}
+
+impl ScriptSettings {
+    /// Loads or spawns the script and gives it the budget.
+    pub fn load(&self, library: &ScriptLibrary) -> Result<ScriptPlayer, ScriptError> {
+        let mut script = if self.out_of_process {
+            ScriptPlayer::spawn(library)?
+        } else {
+            ScriptPlayer::load(library)?
+        };
+        script.set_budget(self.budget);
+        Ok(script)
+    }
+}
+
+/// Timing of a script over a match.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ScriptStats {
+    pub calls: u64,
+    pub overruns: u32,
+    pub total_time: Duration,
+    pub max_time: Duration,
+}
+
+impl ScriptStats {
+    fn record(&mut self, elapsed: Duration, budget: &TimeBudget) -> bool {
+        self.calls += 1;
+        self.total_time += elapsed;
+        self.max_time = self.max_time.max(elapsed);
+        let overrun = elapsed > budget.per_call;
+        if overrun {
+            self.overruns += 1;
+        }
+        overrun
+    }
+
+    pub fn average_time(&self) -> Duration {
+        if self.calls == 0 {
+            return Duration::default();
+        }
+        self.total_time / self.calls as u32
+    }
+}
+
+impl fmt::Display for ScriptStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} calls, avg {:.3} ms, max {:.3} ms, {} over budget",
+            self.calls,
+            self.average_time().as_secs_f64() * 1000.0,
+            self.max_time.as_secs_f64() * 1000.0,
+            self.overruns
+        )
+    }
+}
+
+/// **********************************************************************
+/// A `ScriptPlayer` is a loaded AI script. The library is opened once,
+/// checked, and `calculate_move` is looked up once; after that each tick
+/// is a plain function call.
+///
+/// A stateful script gets its `init` called before its first move of a
+/// match and `destroy` at `end_match`, or when the player is dropped.
+///
+/// Alternatively the script can run out of process, in a copy of the
+/// game started with `--script-worker`. Then even a segfault in the
+/// script only takes down the worker, and the script forfeits.
+/// **********************************************************************
+pub struct ScriptPlayer {
+    library: ScriptLibrary,
+    backend: Backend,
+    budget: TimeBudget,
+    stats: ScriptStats,
+}
+
+enum Backend {
+    InProcess {
+        entry: Entry,
+        // Has to outlive `entry`, which points into it.
+        _library: Library,
+    },
+    OutOfProcess(WorkerProcess),
+}
+
+enum Entry {
+    Stateless(CalculateMoveFunc),
+    Stateful(Lifecycle),
+}
+
+/// The functions of a stateful script, and its state.
+struct Lifecycle {
+    init: InitFunc,
+    calculate_move: StatefulMoveFunc,
+    on_point_scored: Option<PointScoredFunc>,
+    destroy: DestroyFunc,
+    // What `init` returned, null outside of a match
+    state: *mut c_void,
+}
+
+impl Lifecycle {
+    /// The state for this match, from `init` if the match just started.
+    fn state(&mut self, game: &GameState, p1: bool) -> Result<*mut c_void, ScriptError> {
+        if self.state.is_null() {
+            let info = MatchInfo {
+                p1,
+                field_width: game.screen_width,
+                field_height: game.screen_height,
+                seed: game.config.seed,
+                rules: game.config.rules,
+            };
+            let init = self.init;
+            self.state = unsafe { init(&info) };
+            if self.state.is_null() {
+                return Err(ScriptError::Panicked("in init".to_string()));
+            }
+        }
+        Ok(self.state)
+    }
+
+    fn destroy(&mut self) {
+        if !self.state.is_null() {
+            let (destroy, state) = (self.destroy, self.state);
+            self.state = ptr::null_mut();
+            unsafe { destroy(state) };
+        }
+    }
+}
+
+impl ScriptPlayer {
+    /// Loads the compiled script and checks that it works: it has to be
+    /// built against our `SCRIPT_ABI_VERSION`, and then `add(1, 3)` has to
+    /// return 4. Nothing else in it is called before the version matches.
+    pub fn load(script: &ScriptLibrary) -> Result<ScriptPlayer, ScriptError> {
+        let library = Library::new(&script.path).map_err(|e| ScriptError::Load(e.to_string()))?;
+        let entry = &script.entry;
+
+        unsafe {
+            //The script has to be built against the same structs as the game.
+            //Scripts without the export are treated as version 0.
+            let version = match library.get::<AbiVersionFunc>(entry.abi_version.as_bytes()) {
+                Ok(func) => func(),
+                Err(_) => 0,
+            };
+            if version != SCRIPT_ABI_VERSION {
+                return Err(ScriptError::AbiMismatch { script: version, game: SCRIPT_ABI_VERSION });
+            }
+
+            //Tests the code, 1 + 3 = 4. Mostly to check connectivity
+            let add: AddFunc = *library.get::<AddFunc>(entry.add.as_bytes())
+                .map_err(|_| ScriptError::MissingSymbol(entry.add.clone()))?;
+            let answer = add(1, 3);
+            if answer != 4 {
+                return Err(ScriptError::TestFailed(answer));
+            }
+
+            let missing = |symbol: &String| ScriptError::MissingSymbol(symbol.clone());
+            //Exporting init makes it a stateful script
+            let functions = match library.get::<InitFunc>(entry.init.as_bytes()) {
+                Ok(init) => Entry::Stateful(Lifecycle {
+                    init: *init,
+                    calculate_move: *library.get::<StatefulMoveFunc>(entry.calculate_move.as_bytes())
+                        .map_err(|_| missing(&entry.calculate_move))?,
+                    on_point_scored: library.get::<PointScoredFunc>(entry.on_point_scored.as_bytes()).ok().map(|func| *func),
+                    destroy: *library.get::<DestroyFunc>(entry.destroy.as_bytes()).map_err(|_| missing(&entry.destroy))?,
+                    state: ptr::null_mut(),
+                }),
+                Err(_) => Entry::Stateless(*library.get::<CalculateMoveFunc>(entry.calculate_move.as_bytes())
+                    .map_err(|_| missing(&entry.calculate_move))?),
+            };
+
+            Ok(ScriptPlayer {
+                library: script.clone(),
+                backend: Backend::InProcess {
+                    entry: functions,
+                    _library: library,
+                },
+                budget: TimeBudget::default(),
+                stats: ScriptStats::default(),
+            })
+        }
+    }
+
+    /// Starts a worker process that loads the script and answers for it.
+    /// The worker runs the same checks as `load`.
+    pub fn spawn(script: &ScriptLibrary) -> Result<ScriptPlayer, ScriptError> {
+        let worker = WorkerProcess::spawn(script)?;
+        Ok(ScriptPlayer {
+            library: script.clone(),
+            backend: Backend::OutOfProcess(worker),
+            budget: TimeBudget::default(),
+            stats: ScriptStats::default(),
+        })
+    }
+
+    pub fn library(&self) -> &ScriptLibrary {
+        &self.library
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.library.path
+    }
+
+    pub fn name(&self) -> &str {
+        &self.library.name
+    }
+
+    pub fn is_out_of_process(&self) -> bool {
+        matches!(self.backend, Backend::OutOfProcess(_))
+    }
+
+    pub fn set_budget(&mut self, budget: TimeBudget) {
+        self.budget = budget;
+    }
+
+    pub fn stats(&self) -> &ScriptStats {
+        &self.stats
+    }
+
+    /// Asks the script for its move this tick.
+    /// `p1` tells the script whether it controls player 1. Scripts answer
+    /// in the coordinates of their own side; the input returned here is
+    /// in world coordinates.
+    /// The call is timed against the script's `TimeBudget`. Only the
+    /// script itself is timed: a worker reports how long the script took,
+    /// without the round trip to it.
+    pub fn generate_input(&mut self, game: &GameState, p1: bool) -> Result<InputState, ScriptError> {
+        let (input, elapsed) = self.call_script(game, p1)?;
+        let overrun = self.stats.record(elapsed, &self.budget);
+
+        if overrun && self.stats.overruns > self.budget.allowed_overruns {
+            return match self.budget.penalty {
+                OverrunPenalty::NoInput => Ok(InputState::default()),
+                OverrunPenalty::Forfeit => Err(ScriptError::OverBudget { overruns: self.stats.overruns }),
+            };
+        }
+        Ok(input)
+    }
+
+    /// Tells a stateful script that a point was scored, once the field
+    /// has been reset.
+    pub fn point_scored(&mut self, game: &GameState, p1: bool, scorer: Side) -> Result<(), ScriptError> {
+        let lifecycle = match &mut self.backend {
+            Backend::InProcess { entry: Entry::Stateful(lifecycle), .. } => lifecycle,
+            Backend::InProcess { .. } => return Ok(()),
+            Backend::OutOfProcess(worker) => return worker.point_scored(game, p1, scorer, self.budget.hang_timeout),
+        };
+        let on_point_scored = match lifecycle.on_point_scored {
+            Some(func) => func,
+            None => return Ok(()),
+        };
+        let state = lifecycle.state(game, p1)?;
+        let (my_score, opponent_score) = if p1 { (game.score1, game.score2) } else { (game.score2, game.score1) };
+        let point = PointScored {
+            i_scored: (scorer == Side::Left) == p1,
+            my_score,
+            opponent_score,
+        };
+        match unsafe { on_point_scored(state, &point) } {
+            MoveStatus::Ok => Ok(()),
+            MoveStatus::Panicked => Err(ScriptError::Panicked("in on_point_scored".to_string())),
+        }
+    }
+
+    /// Ends the match for a stateful script: its state is destroyed and
+    /// the next move starts a new match.
+    pub fn end_match(&mut self) {
+        match &mut self.backend {
+            Backend::InProcess { entry: Entry::Stateful(lifecycle), .. } => lifecycle.destroy(),
+            Backend::InProcess { .. } => (),
+            Backend::OutOfProcess(worker) => {
+                if let Err(reason) = worker.end_match(self.budget.hang_timeout) {
+                    eprintln!("Script {} could not end its match: {}", self.library.name, reason);
+                }
+            }
+        }
+    }
+
+    /// The script's move and how long the script took to make it.
+    fn call_script(&mut self, game: &GameState, p1: bool) -> Result<(InputState, Duration), ScriptError> {
+        match &mut self.backend {
+            Backend::InProcess { entry, .. } => {
+                let start = Instant::now();
+                let mut input = InputState::default();
+                let status = match entry {
+                    Entry::Stateless(calculate_move) => {
+                        let calculate_move = *calculate_move;
+                        with_context(game, p1, |context| unsafe { calculate_move(context, &mut input) })
+                    }
+                    Entry::Stateful(lifecycle) => {
+                        let state = lifecycle.state(game, p1)?;
+                        let calculate_move = lifecycle.calculate_move;
+                        with_context(game, p1, |context| unsafe { calculate_move(state, context, &mut input) })
+                    }
+                };
+                match status {
+                    MoveStatus::Ok if p1 => Ok((input, start.elapsed())),
+                    MoveStatus::Ok => Ok((input.mirrored(), start.elapsed())),
+                    MoveStatus::Panicked => Err(ScriptError::Panicked("in calculate_move".to_string())),
+                }
+            }
+            Backend::OutOfProcess(worker) => worker.request(game, p1, self.budget.hang_timeout),
+        }
+    }
+}
+
+impl Drop for ScriptPlayer {
+    fn drop(&mut self) {
+        if let Backend::InProcess { entry: Entry::Stateful(lifecycle), .. } = &mut self.backend {
+            lifecycle.destroy();
+        }
+    }
+}
+
+/// The command line flag that turns the game binary into a script worker.
+pub const WORKER_FLAG: &str = "--script-worker";
+
+/// **********************************************************************
+/// Out-of-process scripts. The host and the worker talk over the worker's
+/// stdin and stdout, one JSON message per line. The script's own prints go
+/// to the worker's stderr, see `protocol_output`.
+/// **********************************************************************
+#[derive(Serialize, Deserialize)]
+enum WorkerRequest {
+    Move { game: GameState, p1: bool },
+    PointScored { game: GameState, p1: bool, scorer: Side },
+    EndMatch,
+}
+
+#[derive(Serialize, Deserialize)]
+enum WorkerReply {
+    Ready,
+    /// The move, and how long the script took to make it.
+    Move(InputState, Duration),
+    Done,
+    Failed(ScriptError),
+}
+
+struct WorkerProcess {
+    child: Child,
+    stdin: ChildStdin,
+    // Lines from the worker's stdout, read on a separate thread so the
+    // host can give up on a worker that hangs.
+    replies: Receiver<io::Result<String>>,
+}
+
+impl WorkerProcess {
+    fn spawn(script: &ScriptLibrary) -> Result<WorkerProcess, ScriptError> {
+        let exe = env::current_exe().map_err(|e| ScriptError::Load(e.to_string()))?;
+        let mut child = Command::new(exe)
+            .arg(WORKER_FLAG)
+            .arg(serde_json::to_string(script).unwrap())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| ScriptError::Load(format!("could not start worker: {}", e)))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut worker = WorkerProcess { child, stdin, replies };
+
+        match worker.read_reply(Duration::from_secs(10))? {
+            WorkerReply::Ready => Ok(worker),
+            WorkerReply::Failed(reason) => Err(reason),
+            WorkerReply::Move(..) | WorkerReply::Done => Err(ScriptError::Crashed("worker answered before it was asked".to_string())),
+        }
+    }
+
+    fn request(&mut self, game: &GameState, p1: bool, timeout: Duration) -> Result<(InputState, Duration), ScriptError> {
+        match self.send(&WorkerRequest::Move { game: game.clone(), p1 }, timeout)? {
+            WorkerReply::Move(input, elapsed) => Ok((input, elapsed)),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn point_scored(&mut self, game: &GameState, p1: bool, scorer: Side, timeout: Duration) -> Result<(), ScriptError> {
+        match self.send(&WorkerRequest::PointScored { game: game.clone(), p1, scorer }, timeout)? {
+            WorkerReply::Done => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn end_match(&mut self, timeout: Duration) -> Result<(), ScriptError> {
+        match self.send(&WorkerRequest::EndMatch, timeout)? {
+            WorkerReply::Done => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn send(&mut self, request: &WorkerRequest, timeout: Duration) -> Result<WorkerReply, ScriptError> {
+        let mut line = serde_json::to_string(request).unwrap();
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| self.crashed(e.to_string()))?;
+        self.read_reply(timeout)
+    }
+
+    fn read_reply(&mut self, timeout: Duration) -> Result<WorkerReply, ScriptError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.replies.recv_timeout(remaining) {
+                Ok(Ok(line)) => match serde_json::from_str(&line) {
+                    Ok(reply) => return Ok(reply),
+                    // Only where the script shares the pipe, see `protocol_output`
+                    Err(_) => println!("{}", line),
+                },
+                Ok(Err(e)) => return Err(self.crashed(e.to_string())),
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = self.child.kill();
+                    return Err(ScriptError::Crashed(format!("no answer within {:?}, worker killed", timeout)));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.crashed("worker closed the pipe".to_string())),
+            }
+        }
+    }
+
+    /// Describes why the worker stopped answering, including how it exited.
+    /// The pipe closes slightly before the process is reaped, so we give
+    /// it a moment to report its exit status.
+    fn crashed(&mut self, reason: String) -> ScriptError {
+        for _ in 0..10 {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return ScriptError::Crashed(format!("worker exited with {}", status));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        ScriptError::Crashed(reason)
+    }
+}
+
+/// The error for a reply that doesn't answer the request.
+fn unexpected(reply: WorkerReply) -> ScriptError {
+    match reply {
+        WorkerReply::Failed(reason) => reason,
+        WorkerReply::Ready => ScriptError::Crashed("worker restarted".to_string()),
+        WorkerReply::Move(..) | WorkerReply::Done => ScriptError::Crashed("worker answered something else".to_string()),
+    }
+}
+
+impl Drop for WorkerProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn send_reply(stdout: &mut impl Write, reply: &WorkerReply) -> io::Result<()> {
+    let mut line = serde_json::to_string(reply).unwrap();
+    line.push('\n');
+    stdout.write_all(line.as_bytes())?;
+    stdout.flush()
+}
+
+/// Where the worker sends its replies. Scripts print to stdout too, and
+/// a `print!` without a newline would run into the next reply, so on Unix
+/// the worker keeps the pipe to the host to itself and points its stdout
+/// at stderr before the script is loaded.
+#[cfg(unix)]
+fn protocol_output() -> io::Result<std::fs::File> {
+    use std::os::unix::io::FromRawFd;
+    io::stdout().flush()?;
+    unsafe {
+        let pipe = libc::dup(1);
+        if pipe < 0 || libc::dup2(2, 1) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(std::fs::File::from_raw_fd(pipe))
+    }
+}
+
+/// Elsewhere the replies share stdout with the script, and the host
+/// passes on any line that isn't a reply.
+#[cfg(not(unix))]
+fn protocol_output() -> io::Result<io::Stdout> {
+    Ok(io::stdout())
+}
+
+/// The main loop of a worker process: load the script described by the
+/// JSON `ScriptLibrary` in `script`, then answer one move per request
+/// until the host closes stdin.
+pub fn run_worker(script: &str) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut replies = protocol_output()?;
+
+    let library: ScriptLibrary = serde_json::from_str(script)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut script = match ScriptPlayer::load(&library) {
+        Ok(script) => script,
+        Err(reason) => return send_reply(&mut replies, &WorkerReply::Failed(reason)),
+    };
+    send_reply(&mut replies, &WorkerReply::Ready)?;
+
+    for line in stdin.lock().lines() {
+        let request: WorkerRequest = match serde_json::from_str(&line?) {
+            Ok(request) => request,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let reply = match request {
+            //Only the host keeps the time budget, it knows the one the user chose
+            WorkerRequest::Move { game, p1 } => match script.call_script(&game, p1) {
+                Ok((input, elapsed)) => WorkerReply::Move(input, elapsed),
+                Err(reason) => WorkerReply::Failed(reason),
+            },
+            WorkerRequest::PointScored { game, p1, scorer } => match script.point_scored(&game, p1, scorer) {
+                Ok(()) => WorkerReply::Done,
+                Err(reason) => WorkerReply::Failed(reason),
+            },
+            WorkerRequest::EndMatch => {
+                script.end_match();
+                WorkerReply::Done
+            }
+        };
+        send_reply(&mut replies, &reply)?;
+    }
+    Ok(())
+}
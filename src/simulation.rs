@@ -0,0 +1,474 @@
+//! The game itself, without any window attached.
+//! Everything in here is plain data and plain functions, so a
+//! `Simulation` can be stepped thousands of times per second on a
+//! headless box (AI training, testing) as well as from the ggez loop.
+
+use std::cmp::Ordering;
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::physics::{self, ball_halt, first_contact, resolve_collisions, stop_tunneling, update_object_position, Bounds, Path};
+
+/// The `InputState` is exactly what it sounds like, it just keeps track of
+/// the user's input state so that we turn keyboard events into something
+/// state-based and device-independent. Scripts return the same struct,
+/// so it lives in the shared `script_abi` crate.
+pub use script_abi::InputState;
+
+/// The sizes, speeds and masses a match is played with. Scripts get to
+/// read them, so they live in `script_abi` too.
+pub use script_abi::GameRules;
+
+/// *********************************************************************
+/// Now we define our PhysObjects.
+/// A PhysObject is anything in the game world.
+/// **********************************************************************
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PhysType {
+    Player,
+    Ball
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhysObject {
+    pub tag: PhysType,
+    pub id: f32,
+    pub hold: f32,
+    pub pos: (f32, f32),
+    pub x_velocity: f32,
+    pub y_velocity: f32,
+    pub bbox_size: f32,
+}
+
+impl PhysObject {
+    /// Distance between the centers of two objects.
+    pub fn distance_to(&self, other: &PhysObject) -> f32 {
+        let dx = other.pos.0 - self.pos.0;
+        let dy = other.pos.1 - self.pos.1;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// *********************************************************************
+/// Now we have some constructor functions for different PhysObject.
+/// Everything random about a new field comes from the game's own
+/// seeded generator, so the same seed always gives the same field.
+/// **********************************************************************
+fn create_player(spawn_pos: (f32, f32), player_id: f32, radius: f32) -> PhysObject {
+    PhysObject {
+        tag: PhysType::Player,
+        id: player_id,
+        hold: 0.0,
+        pos: spawn_pos,
+        x_velocity: 0.0,
+        y_velocity: 0.0,
+        bbox_size: radius
+    }
+}
+
+/// Hands out ball ids, unique for the whole match, and nudges each pair
+/// of balls up or down by up to `jitter`, the same on both sides.
+struct BallSpawner<'a> {
+    rng: &'a mut Pcg32,
+    next_id: &'a mut f32,
+    jitter: f32,
+    spacing: f32,
+    radius: f32,
+}
+
+impl BallSpawner<'_> {
+    fn create_balls(&mut self, balls_num: f32) -> Vec<PhysObject> {
+        let mut balls = Vec::new();
+        let distance = 100.0;
+        balls.append(&mut self.create_balls_collumn((balls_num / 2.0).ceil(), distance));
+        balls.append(&mut self.create_balls_collumn((balls_num / 2.0).floor(), distance + self.spacing));
+
+        balls
+    }
+
+    fn create_balls_collumn(&mut self, balls_num: f32, distance: f32) -> Vec<PhysObject> {
+        let space = self.spacing;
+        let mut space_iter = -((balls_num - 1.0) * space) / 2.0;
+        let mut balls = Vec::new();
+        for _ in 0..balls_num as i32 {
+            balls.append(&mut self.create_ball_pair(distance, space_iter));
+            space_iter += space;
+        }
+
+        balls
+    }
+
+    fn create_ball_pair(&mut self, x: f32, y: f32) -> Vec<PhysObject> {
+        let y = if self.jitter > 0.0 {
+            y + self.rng.gen_range(-self.jitter, self.jitter)
+        } else {
+            y
+        };
+        vec![
+            self.create_ball((-x, y)),
+            self.create_ball((x, y)),
+        ]
+    }
+
+    fn create_ball(&mut self, pos: (f32, f32)) -> PhysObject {
+        let id = *self.next_id;
+        *self.next_id += 1.0;
+        PhysObject {
+            tag: PhysType::Ball,
+            id,
+            hold: 0.0,
+            pos,
+            x_velocity: 0.0,
+            y_velocity: 0.0,
+            bbox_size: self.radius
+        }
+    }
+}
+
+fn ball_id_to_elem(balls: &[PhysObject], id: f32) -> Option<usize> {
+    balls.iter().position(|ball| ball.id == id)
+}
+
+fn ball_follow(player: &PhysObject, balls: &mut [PhysObject], offset: f32) {
+    if let Some(x) = ball_id_to_elem(balls, player.hold) {
+        balls[x].pos.0 = player.pos.0 + offset;
+        balls[x].pos.1 = player.pos.1;
+        // Moves with the player, which matters for what bounces off it
+        balls[x].x_velocity = player.x_velocity;
+        balls[x].y_velocity = player.y_velocity;
+        balls[x].hold = player.id;
+    }
+}
+
+/// *********************************************************************
+/// Now we handle what players do with the balls. How things move and
+/// bounce is in the `physics` module.
+/// **********************************************************************
+/// Picks up, drops and throws. Moving is up to `physics::accelerate`.
+/// `forward` is the x direction the player faces: 1 on the left, -1 on the right.
+fn player_handle_input(player: &mut PhysObject, input: &InputState, balls: &mut [PhysObject], rules: &GameRules, forward: f32) {
+    if player.hold != 0.0 && input.throw {
+        ball_throw(player, balls, input, rules, forward);
+    }
+    if player.hold == 0.0 && input.holdball {
+        ball_pickup(player, balls, rules.pickup_reach);
+    } else if player.hold != 0.0 && !input.holdball {
+        ball_drop(player, balls);
+    }
+}
+
+/// Collisions keep balls at arm's length, so a player reaches `reach`
+/// further than touching to pick one up.
+fn collision_check(player: &PhysObject, balls: &[PhysObject], reach: f32) -> Vec<f32> {
+    let mut coll_balls = Vec::new();
+    for ball in balls {
+        if ball.distance_to(player) < (player.bbox_size + ball.bbox_size + reach) {
+            coll_balls.push(ball.id)
+        }
+    }
+    coll_balls
+}
+
+fn ball_pickup(player: &mut PhysObject, balls: &[PhysObject], reach: f32) {
+    if player.hold != 0.0 {
+        return; //already holding
+    }
+    //A ball the player let go of is out of reach until it stops
+    let reachable = collision_check(player, balls, reach).into_iter()
+        .find(|id| balls.iter().any(|ball| ball.id == *id && ball.hold != player.id));
+    if let Some(id) = reachable {
+        player.hold = id;
+    }
+}
+
+fn ball_drop(player: &mut PhysObject, balls: &mut [PhysObject]) {
+    if let Some(x) = ball_id_to_elem(balls, player.hold) {
+        balls[x].x_velocity = player.x_velocity;
+        balls[x].y_velocity = player.y_velocity;
+    }
+    player.hold = 0.0;
+}
+
+fn ball_throw(player: &mut PhysObject, balls: &mut [PhysObject], input: &InputState, rules: &GameRules, forward: f32) {
+    if let Some(x) = ball_id_to_elem(balls, player.hold) {
+        let [aim_x, aim_y] = input.aim;
+        let length = (aim_x * aim_x + aim_y * aim_y).sqrt();
+        //Scripts can send anything, so nonsense aims straight ahead
+        let (dir_x, dir_y) = if length.is_finite() && length > 0.0 { (aim_x / length, aim_y / length) } else { (forward, 0.0) };
+        let charge = if input.charge.is_nan() { 0.0 } else { input.charge.clamp(0.0, 1.0) };
+        let speed = rules.min_throw_speed + charge * (rules.max_throw_speed - rules.min_throw_speed);
+        balls[x].x_velocity = dir_x * speed;
+        balls[x].y_velocity = dir_y * speed;
+    }
+    player.hold = 0.0;
+}
+
+/// When during the step `player` was first hit by a ball thrown by
+/// `alligment`, if it was. A thrown ball is followed along its whole path,
+/// so it hits however fast it flew; a ball still in the thrower's hands
+/// is only where its player put it, so it has to touch at the end.
+fn collision_check_score(player: &PhysObject, player_path: &Path, balls: &[PhysObject], paths: &[Path], alligment: f32, held: [f32; 2], dt: f32) -> Option<f32> {
+    balls.iter().zip(paths)
+        .filter(|(ball, _)| ball.hold == alligment)
+        .filter_map(|(ball, path)| {
+            let reach = player.bbox_size + ball.bbox_size;
+            let swept = if held.contains(&ball.id) { None } else { first_contact(player_path, path, reach) };
+            swept.or_else(|| (ball.distance_to(player) < reach).then_some(dt))
+        })
+        .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+}
+
+/// Which half of the field a player defends.
+/// Player 1 (red) is always on the left, player 2 (blue) on the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// When a match is over.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WinCondition {
+    /// The match never ends.
+    Endless,
+    /// The first player to this many points wins.
+    FirstTo(i32),
+    /// The match ends after this many seconds of game time; whoever is
+    /// ahead wins.
+    TimeLimit(f32),
+    /// At most this many points (rounds) are played; the match ends as soon
+    /// as one player has more than half of them.
+    BestOf(i32),
+}
+
+/// How a finished match ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Winner(Side),
+    Draw,
+}
+
+impl Outcome {
+    /// Whoever has more points wins.
+    pub fn by_score(score1: i32, score2: i32) -> Outcome {
+        match score1.cmp(&score2) {
+            Ordering::Greater => Outcome::Winner(Side::Left),
+            Ordering::Less => Outcome::Winner(Side::Right),
+            Ordering::Equal => Outcome::Draw,
+        }
+    }
+}
+
+/// The settings a match is played with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GameConfig {
+    pub width: f32,
+    pub height: f32,
+    /// How far, in pixels, each pair of balls may be moved up or down
+    /// from its usual spot when the field is reset.
+    pub spawn_jitter: f32,
+    pub win_condition: WinCondition,
+    /// Seed for everything random in a match. The same seed and the same
+    /// inputs always play out exactly the same.
+    pub seed: u64,
+    /// How many smaller steps the physics takes per step of the game.
+    /// More is smoother and more precise, and slower.
+    pub substeps: u32,
+    pub rules: GameRules,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            width: 640.0,
+            height: 480.0,
+            spawn_jitter: 8.0,
+            win_condition: WinCondition::FirstTo(5),
+            seed: 0,
+            substeps: 1,
+            rules: GameRules::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameState {
+    pub player1: PhysObject,
+    pub player2: PhysObject,
+    pub balls: Vec<PhysObject>,
+    pub score1: i32,
+    pub score2: i32,
+    pub screen_width: f32,
+    pub screen_height: f32,
+    pub input1: InputState,
+    pub input2: InputState,
+    pub config: GameConfig,
+    /// Game time played so far, in seconds.
+    pub time: f32,
+    /// Seeded from `config.seed`; all randomness in the game comes from here.
+    pub rng: Pcg32,
+    next_ball_id: f32,
+}
+
+impl GameState {
+    pub fn new(width: f32, height: f32) -> GameState {
+        GameState::with_config(GameConfig {
+            width,
+            height,
+            ..GameConfig::default()
+        })
+    }
+
+    pub fn with_config(config: GameConfig) -> GameState {
+        let mut game = GameState {
+            player1: create_player((0.0, 0.0), 1.0, config.rules.player_radius),
+            player2: create_player((0.0, 0.0), 2.0, config.rules.player_radius),
+            balls: Vec::new(),
+            score1: 0,
+            score2: 0,
+            screen_width: config.width,
+            screen_height: config.height,
+            input1: InputState::default(),
+            input2: InputState::default(),
+            config,
+            time: 0.0,
+            rng: Pcg32::seed_from_u64(config.seed),
+            next_ball_id: 3.0,
+        };
+        game.reset_field();
+        game
+    }
+
+    /// Puts both players back in their starting positions and lays out a
+    /// fresh set of balls. The score is left untouched.
+    pub fn reset_field(&mut self) {
+        let width = self.screen_width;
+        let rules = self.config.rules;
+        self.player1 = create_player((-3.0 * width / 8.0, 0.0), 1.0, rules.player_radius);
+        self.player2 = create_player((3.0 * width / 8.0, 0.0), 2.0, rules.player_radius);
+        let mut spawner = BallSpawner {
+            rng: &mut self.rng,
+            next_id: &mut self.next_ball_id,
+            jitter: self.config.spawn_jitter,
+            spacing: rules.ball_spacing,
+            radius: rules.ball_radius,
+        };
+        self.balls = spawner.create_balls(rules.balls_per_side as f32);
+    }
+
+    /// Moves and bounces everything for `dt` seconds. Returns the side
+    /// that scored, if a player was hit.
+    fn physics_step(&mut self, dt: f32) -> Option<Side> {
+        let rules = self.config.rules;
+        physics::accelerate(&mut self.player1, &self.input1, &rules, dt);
+        physics::accelerate(&mut self.player2, &self.input2, &rules, dt);
+
+        // First the players, each in its own half...
+        let (half_width, half_height) = (self.screen_width / 2.0, self.screen_height / 2.0);
+        let field = Bounds { left: -half_width, right: half_width, bottom: -half_height, top: half_height };
+        let path1 = update_object_position(&mut self.player1, Bounds { right: 0.0, ..field }, &rules, dt);
+        let path2 = update_object_position(&mut self.player2, Bounds { left: 0.0, ..field }, &rules, dt);
+        // Then the balls, which bounce off the walls with their edge!
+        let mut paths = Vec::with_capacity(self.balls.len());
+        for ball in &mut self.balls {
+            paths.push(update_object_position(ball, field.inset(ball.bbox_size), &rules, dt));
+            ball_halt(ball, &rules, dt)
+        }
+
+        ball_follow(&self.player1, &mut self.balls, rules.hold_offset);
+        ball_follow(&self.player2, &mut self.balls, -rules.hold_offset);
+        for (ball, path) in self.balls.iter_mut().zip(&paths) {
+            stop_tunneling(&mut self.player1, &path1, ball, path, &rules);
+            stop_tunneling(&mut self.player2, &path2, ball, path, &rules);
+        }
+        resolve_collisions(&mut self.player1, &mut self.player2, &mut self.balls, &rules);
+
+        // Whoever was hit first loses the point
+        let held = [self.player1.hold, self.player2.hold];
+        let hit1 = collision_check_score(&self.player1, &path1, &self.balls, &paths, 2.0, held, dt);
+        let hit2 = collision_check_score(&self.player2, &path2, &self.balls, &paths, 1.0, held, dt);
+        match (hit1, hit2) {
+            (Some(time1), Some(time2)) if time2 < time1 => Some(Side::Left),
+            (Some(_), _) => Some(Side::Right),
+            (None, Some(_)) => Some(Side::Left),
+            (None, None) => None,
+        }
+    }
+
+    /// How the match ended, or `None` while it is still going.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let (score1, score2) = (self.score1, self.score2);
+        let over = match self.config.win_condition {
+            WinCondition::Endless => false,
+            WinCondition::FirstTo(points) => score1 >= points || score2 >= points,
+            WinCondition::TimeLimit(seconds) => self.time >= seconds,
+            WinCondition::BestOf(rounds) => {
+                2 * score1 > rounds || 2 * score2 > rounds || score1 + score2 >= rounds
+            }
+        };
+        if over {
+            Some(Outcome::by_score(score1, score2))
+        } else {
+            None
+        }
+    }
+}
+
+/// Length of a tick in seconds. The window runs at 60 ticks per second
+/// and headless matches use the same tick, so both play the same game.
+pub const TICK: f32 = 1.0 / 60.0;
+
+/// **********************************************************************
+/// The `Simulation` owns a `GameState` and advances it one tick at a time.
+/// This is all the game logic there is; the ggez `MainState` only feeds
+/// it inputs and draws the result.
+/// **********************************************************************
+pub struct Simulation {
+    pub game: GameState,
+}
+
+impl Simulation {
+    pub fn new(width: f32, height: f32) -> Simulation {
+        Simulation {
+            game: GameState::new(width, height),
+        }
+    }
+
+    pub fn with_config(config: GameConfig) -> Simulation {
+        Simulation {
+            game: GameState::with_config(config),
+        }
+    }
+
+    /// Advances the game by one fixed `TICK`. Ticking is deterministic:
+    /// the same seed and the same inputs give bit-identical games.
+    pub fn tick(&mut self, input1: InputState, input2: InputState) -> Option<Side> {
+        self.step(input1, input2, TICK)
+    }
+
+    /// Advances the game by `dt` seconds using the given inputs.
+    /// Returns the side that scored, if a point was scored this tick,
+    /// in which case the field has already been reset.
+    pub fn step(&mut self, input1: InputState, input2: InputState, dt: f32) -> Option<Side> {
+        let game = &mut self.game;
+        game.input1 = input1;
+        game.input2 = input2;
+        game.time += dt;
+
+        let rules = game.config.rules;
+        player_handle_input(&mut game.player1, &game.input1, &mut game.balls, &rules, 1.0);
+        player_handle_input(&mut game.player2, &game.input2, &mut game.balls, &rules, -1.0);
+
+        // The physics takes its own, smaller steps; the first hit ends it
+        let substeps = game.config.substeps.max(1);
+        let scorer = (0..substeps).find_map(|_| game.physics_step(dt / substeps as f32))?;
+        match scorer {
+            Side::Left => game.score1 += 1,
+            Side::Right => game.score2 += 1,
+        }
+        game.reset_field();
+        Some(scorer)
+    }
+}
@@ -0,0 +1,213 @@
+//! Round-robin tournaments between scripts, played headless.
+//! Every script meets every other script on both sides, once for each
+//! seed, so neither the side nor one lucky ball layout decides it.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::headless::{run_match, MatchResult};
+use crate::replay::Replay;
+use crate::script_build::ScriptLibrary;
+use crate::scripting::ScriptSettings;
+use crate::simulation::{GameConfig, Outcome, Side};
+
+/// How one script did over the whole tournament.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Standing {
+    pub name: String,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Matches lost because the script failed, to load or while playing.
+    pub forfeits: u32,
+    pub points_for: i32,
+    pub points_against: i32,
+}
+
+impl Standing {
+    /// Tournament points: 3 for a win, 1 for a draw.
+    pub fn points(&self) -> u32 {
+        3 * self.wins + self.draws
+    }
+
+    fn record(&mut self, scored: i32, conceded: i32, outcome: Option<bool>, failed: bool) {
+        self.played += 1;
+        if failed {
+            self.forfeits += 1;
+        }
+        self.points_for += scored;
+        self.points_against += conceded;
+        match outcome {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+}
+
+/// One match of a tournament.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub player1: String,
+    pub player2: String,
+    pub result: MatchResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentResult {
+    /// Best first.
+    pub standings: Vec<Standing>,
+    pub matches: Vec<MatchRecord>,
+}
+
+/// How a tournament is played.
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    /// The settings of every match. Its seed is the first of the seeds
+    /// the pairings are played with: `seed`, `seed + 1`, ...
+    pub game: GameConfig,
+    /// How many seeds every pairing is played with, on each side.
+    pub seeds: u32,
+    pub max_time: f32,
+    pub scripts: ScriptSettings,
+    /// Where to save a replay of every match, if anywhere.
+    pub replay_dir: Option<PathBuf>,
+}
+
+impl Tournament {
+    /// Plays every pairing of `scripts`. Each match gets freshly loaded
+    /// scripts; a script that can't be loaded forfeits that match without
+    /// it being played, like one that fails while playing.
+    pub fn run(&self, scripts: &[ScriptLibrary]) -> TournamentResult {
+        let mut standings: Vec<Standing> = scripts.iter()
+            .map(|script| Standing { name: script.name.clone(), ..Standing::default() })
+            .collect();
+        let mut matches = Vec::new();
+
+        for (index1, library1) in scripts.iter().enumerate() {
+            for (index2, library2) in scripts.iter().enumerate() {
+                if index1 == index2 {
+                    continue;
+                }
+                for round in 0..self.seeds {
+                    let config = GameConfig { seed: self.game.seed.wrapping_add(round as u64), ..self.game };
+                    let (result, replay) = self.play(library1, library2, config);
+                    println!("{} vs {}: {}", library1.name, library2.name, result);
+                    if let Some(replay) = replay {
+                        self.save_replay(&replay, config.seed);
+                    }
+
+                    let p1_won = match result.outcome {
+                        Outcome::Winner(side) => Some(side == Side::Left),
+                        Outcome::Draw => None,
+                    };
+                    standings[index1].record(result.score1, result.score2, p1_won, result.failure1.is_some());
+                    standings[index2].record(result.score2, result.score1, p1_won.map(|won| !won), result.failure2.is_some());
+                    matches.push(MatchRecord {
+                        player1: library1.name.clone(),
+                        player2: library2.name.clone(),
+                        result,
+                    });
+                }
+            }
+        }
+
+        standings.sort_by(|a, b| {
+            b.points().cmp(&a.points())
+                .then((b.points_for - b.points_against).cmp(&(a.points_for - a.points_against)))
+                .then(a.name.cmp(&b.name))
+        });
+        TournamentResult { standings, matches }
+    }
+
+    /// Plays one match. There is no replay of a match that wasn't played.
+    fn play(&self, library1: &ScriptLibrary, library2: &ScriptLibrary, config: GameConfig) -> (MatchResult, Option<Replay>) {
+        let load = |library: &ScriptLibrary| {
+            self.scripts.load(library).map_err(|reason| {
+                eprintln!("Script {} could not be loaded: {}", library.name, reason);
+                reason
+            })
+        };
+        match (load(library1), load(library2)) {
+            (Ok(mut script1), Ok(mut script2)) => {
+                let (result, replay) = run_match(config, Some(&mut script1), Some(&mut script2), self.max_time);
+                (result, Some(replay))
+            }
+            (script1, script2) => (MatchResult::not_played(config.seed, script1.err(), script2.err()), None),
+        }
+    }
+
+    fn save_replay(&self, replay: &Replay, seed: u64) {
+        if let Some(dir) = &self.replay_dir {
+            let path = dir.join(format!("{}-vs-{}-{}.json", replay.player1, replay.player2, seed));
+            if let Err(e) = replay.save(&path) {
+                eprintln!("Could not save the replay {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl TournamentResult {
+    /// The standings as a table for the terminal.
+    pub fn standings_table(&self) -> String {
+        let width = self.standings.iter().map(|standing| standing.name.len()).max().unwrap_or(0).max(6);
+        let mut table = format!("{:<width$}  {:>3} {:>3} {:>3} {:>3} {:>3} {:>7} {:>4}\n", "Script", "P", "W", "D", "L", "F", "Goals", "Pts", width = width);
+        for standing in &self.standings {
+            let _ = writeln!(
+                table,
+                "{:<width$}  {:>3} {:>3} {:>3} {:>3} {:>3} {:>3}-{:<3} {:>4}",
+                standing.name,
+                standing.played,
+                standing.wins,
+                standing.draws,
+                standing.losses,
+                standing.forfeits,
+                standing.points_for,
+                standing.points_against,
+                standing.points(),
+                width = width,
+            );
+        }
+        table
+    }
+
+    /// The standings as CSV, one script per row.
+    pub fn standings_csv(&self) -> String {
+        let mut csv = String::from("name,played,wins,draws,losses,forfeits,points_for,points_against,points\n");
+        for standing in &self.standings {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{}",
+                standing.name,
+                standing.played,
+                standing.wins,
+                standing.draws,
+                standing.losses,
+                standing.forfeits,
+                standing.points_for,
+                standing.points_against,
+                standing.points(),
+            );
+        }
+        csv
+    }
+
+    /// Writes the standings as CSV if `path` ends in `.csv`, otherwise
+    /// the standings and every match as JSON.
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let text = if path.extension().is_some_and(|extension| extension == "csv") {
+            self.standings_csv()
+        } else {
+            serde_json::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+}
@@ -0,0 +1,74 @@
+//! Reading the command line: players, subcommands and rules.
+
+use std::fs;
+
+use framslov_melvinj_game::cli::{self, Command, PlayerChoice};
+
+fn parse(args: &[&str]) -> Result<cli::Options, String> {
+    cli::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+fn script(name: &str) -> PlayerChoice {
+    PlayerChoice::Script(name.to_string())
+}
+
+#[test]
+fn no_players_is_two_humans_in_a_window() {
+    let options = parse(&[]).unwrap();
+    assert_eq!(options.command, Command::Play { player1: PlayerChoice::Human, player2: PlayerChoice::Human });
+}
+
+#[test]
+fn human_is_a_keyboard_player_on_either_side() {
+    let options = parse(&["human", "wacko_ai"]).unwrap();
+    assert_eq!(options.command, Command::Play { player1: PlayerChoice::Human, player2: script("wacko_ai") });
+    let options = parse(&["headless", "wacko_ai", "human"]).unwrap();
+    assert_eq!(options.command, Command::Headless { player1: script("wacko_ai"), player2: PlayerChoice::Human });
+    let options = parse(&["wacko_ai"]).unwrap();
+    assert_eq!(options.command, Command::Play { player1: script("wacko_ai"), player2: PlayerChoice::Human });
+}
+
+#[test]
+fn more_than_two_players_is_an_error() {
+    let error = parse(&["wacko_ai", "random_walker", "human"]).unwrap_err();
+    assert!(error.contains("Too many players"), "{}", error);
+    assert!(parse(&["headless", "a", "b", "c"]).is_err());
+}
+
+#[test]
+fn options_go_anywhere() {
+    let options = parse(&["--seed=9", "headless", "--balls=4", "wacko_ai", "--unrated"]).unwrap();
+    assert_eq!(options.command, Command::Headless { player1: script("wacko_ai"), player2: PlayerChoice::Human });
+    assert_eq!(options.game.seed, 9);
+    assert_eq!(options.game.rules.balls_per_side, 4);
+    assert!(!options.rated);
+}
+
+#[test]
+fn unknown_options_and_bad_values_are_errors() {
+    assert!(parse(&["--frobnicate"]).unwrap_err().contains("Unknown option"));
+    assert!(parse(&["--seed=lots"]).is_err());
+    assert!(parse(&["--time-budget=inf"]).is_err());
+    for option in ["--max-time=inf", "--time-limit=infinity", "--width=1e39", "--max-time=NaN"] {
+        let error = parse(&[option]).unwrap_err();
+        assert!(error.contains("finite") || error.contains("more than 0"), "{}: {}", option, error);
+    }
+    assert!(parse(&["--rule=nonsense=1"]).is_err());
+}
+
+#[test]
+fn single_rules_win_over_the_rules_file_wherever_they_are() {
+    let dir = std::env::temp_dir().join(format!("cli-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("rules.toml");
+    fs::write(&file, "ball_drag = 40.0\nmax_throw_speed = 600.0\n").unwrap();
+    let rules_option = format!("--rules={}", file.display());
+
+    let after = parse(&[&rules_option, "--rule=ball_drag=10"]).unwrap();
+    let before = parse(&["--rule=ball_drag=10", &rules_option]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    for options in [after, before] {
+        assert_eq!(options.game.rules.ball_drag, 10.0);
+        assert_eq!(options.game.rules.max_throw_speed, 600.0);
+    }
+}
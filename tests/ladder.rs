@@ -0,0 +1,82 @@
+//! Elo updates on the ladder, and new versions carrying on the rating of
+//! the one before.
+
+use framslov_melvinj_game::ladder::*;
+use framslov_melvinj_game::simulation::{Outcome, Side};
+
+fn script(name: &str, source_hash: &str) -> RatedScript {
+    RatedScript { name: name.to_string(), source_hash: source_hash.to_string() }
+}
+
+fn entry<'a>(ladder: &'a Ladder, script: &RatedScript) -> &'a LadderEntry {
+    ladder.entries.iter().find(|entry| entry.script == *script).unwrap()
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[test]
+fn win_between_new_scripts_moves_16_points() {
+    let (a, b) = (script("a", "1111"), script("b", "2222"));
+    let mut ladder = Ladder::default();
+    ladder.record(&a, &b, Outcome::Winner(Side::Left));
+    assert_eq!(ladder.games, 1);
+    let (winner, loser) = (entry(&ladder, &a), entry(&ladder, &b));
+    assert!(close(winner.rating, START_RATING + 16.0), "{}", winner.rating);
+    assert!(close(loser.rating, START_RATING - 16.0), "{}", loser.rating);
+    assert_eq!((winner.wins, winner.losses, loser.wins, loser.losses), (1, 0, 0, 1));
+    assert_eq!(winner.history[0].opponent, b);
+    assert_eq!(winner.history[0].score, 1.0);
+}
+
+#[test]
+fn draw_between_equals_changes_nothing_and_favourite_loses_more() {
+    let (a, b) = (script("a", "1111"), script("b", "2222"));
+    let mut ladder = Ladder::default();
+    ladder.record(&a, &b, Outcome::Draw);
+    assert!(close(entry(&ladder, &a).rating, START_RATING));
+    assert_eq!(entry(&ladder, &b).draws, 1);
+
+    ladder.record(&a, &b, Outcome::Winner(Side::Right));
+    let favourite = entry(&ladder, &b).rating;
+    let before = entry(&ladder, &a).rating;
+    ladder.record(&a, &b, Outcome::Winner(Side::Left));
+    let gained = entry(&ladder, &a).rating - before;
+    assert!(close(gained, 32.0 * (1.0 - expected_score(before, favourite))));
+    assert!(gained > 16.0, "the underdog only gained {}", gained);
+    //What one gains the other loses
+    let total: f64 = ladder.entries.iter().map(|entry| entry.rating).sum();
+    assert!(close(total, 2.0 * START_RATING));
+}
+
+#[test]
+fn script_playing_itself_is_not_rated() {
+    let a = script("a", "1111");
+    let mut ladder = Ladder::default();
+    ladder.record(&a, &a, Outcome::Winner(Side::Left));
+    assert_eq!(ladder.games, 0);
+    assert!(ladder.entries.is_empty());
+}
+
+#[test]
+fn new_version_starts_from_the_last_one_that_played() {
+    let (old, new, newest) = (script("a", "1111"), script("a", "3333"), script("a", "4444"));
+    let b = script("b", "2222");
+    let mut ladder = Ladder::default();
+    ladder.record(&old, &b, Outcome::Winner(Side::Left));
+    ladder.record(&b, &old, Outcome::Winner(Side::Right));
+    let rating = entry(&ladder, &old).rating;
+
+    ladder.record(&new, &b, Outcome::Draw);
+    let new_entry = entry(&ladder, &new);
+    assert!(close(new_entry.rating - new_entry.history[0].change, rating));
+    assert_eq!(new_entry.played(), 1);
+    assert_eq!(entry(&ladder, &old).played(), 2);
+
+    //The newest carries on from `new`, which played last, not from `old`
+    let carried = entry(&ladder, &new).rating;
+    ladder.record(&newest, &b, Outcome::Draw);
+    let newest_entry = entry(&ladder, &newest);
+    assert!(close(newest_entry.rating - newest_entry.history[0].change, carried));
+}
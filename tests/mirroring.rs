@@ -0,0 +1,72 @@
+//! Player 2 sees the field mirrored, so a script plays both sides the
+//! same way. Mirroring twice has to give back what went in.
+
+use framslov_melvinj_game::scripting::with_context;
+use framslov_melvinj_game::simulation::*;
+
+fn game() -> GameState {
+    let mut game = GameState::with_config(GameConfig { seed: 7, ..GameConfig::default() });
+    game.player1.pos = (-200.0, 40.0);
+    game.player1.x_velocity = 120.0;
+    game.player2.pos = (150.0, -30.0);
+    game.player2.x_velocity = -80.0;
+    game.player2.y_velocity = 25.0;
+    game.score1 = 3;
+    game.score2 = 1;
+    game.input1 = InputState { xaxis1pos: 1.0, aim: [0.5, 0.25], ..InputState::default() };
+    game.input2 = InputState { xaxis1neg: -1.0, yaxis1pos: 0.5, holdball: true, ..InputState::default() };
+    game
+}
+
+#[test]
+fn player_2_sees_itself_on_the_left() {
+    let game = game();
+    with_context(&game, false, |context| {
+        let view = &context.view;
+        assert!(!context.p1);
+        assert_eq!(view.me.pos, [-150.0, -30.0]);
+        assert_eq!((view.me.x_velocity, view.me.y_velocity), (80.0, 25.0));
+        assert_eq!(view.opponent.pos, [200.0, 40.0]);
+        assert_eq!(view.opponent.x_velocity, -120.0);
+        assert_eq!((view.my_score, view.opponent_score), (1, 3));
+        assert_eq!(view.my_input, game.input2.mirrored());
+        assert_eq!(view.opponent_input, game.input1.mirrored());
+        for (seen, ball) in view.balls().iter().zip(&game.balls) {
+            assert_eq!(seen.pos, [-ball.pos.0, ball.pos.1]);
+            assert_eq!(seen.id, ball.id);
+        }
+        assert_eq!(view.balls().len(), game.balls.len());
+        //The unmirrored game is there too
+        assert_eq!(context.game.player2.pos, [150.0, -30.0]);
+    });
+}
+
+#[test]
+fn player_1_sees_the_game_as_it_is() {
+    let game = game();
+    with_context(&game, true, |context| {
+        let view = &context.view;
+        assert_eq!(view.me.pos, [-200.0, 40.0]);
+        assert_eq!(view.opponent.pos, [150.0, -30.0]);
+        assert_eq!((view.my_score, view.opponent_score), (3, 1));
+        assert_eq!(view.my_input, game.input1);
+        let positions: Vec<_> = game.balls.iter().map(|ball| [ball.pos.0, ball.pos.1]).collect();
+        assert_eq!(view.balls().iter().map(|ball| ball.pos).collect::<Vec<_>>(), positions);
+    });
+}
+
+#[test]
+fn mirroring_an_input_twice_gives_it_back() {
+    let inputs = [
+        InputState::default(),
+        InputState { xaxis1pos: 1.0, yaxis1neg: -1.0, ..InputState::default() },
+        InputState { xaxis1pos: 0.25, xaxis1neg: -0.75, holdball: true, throw: true, aim: [-3.0, 2.0], charge: 0.5, ..InputState::default() },
+    ];
+    for input in inputs {
+        assert_eq!(input.mirrored().mirrored(), input);
+    }
+    let right = InputState { xaxis1pos: 1.0, aim: [1.0, 0.5], ..InputState::default() };
+    let left = right.mirrored();
+    assert_eq!((left.xaxis1pos, left.xaxis1neg), (0.0, -1.0));
+    assert_eq!(left.aim, [-1.0, 0.5]);
+}
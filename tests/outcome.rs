@@ -0,0 +1,55 @@
+//! When a match is over and who won it, for every way of winning.
+
+use framslov_melvinj_game::simulation::*;
+
+fn game(win_condition: WinCondition, score1: i32, score2: i32) -> GameState {
+    let mut game = GameState::with_config(GameConfig { win_condition, ..GameConfig::default() });
+    game.score1 = score1;
+    game.score2 = score2;
+    game
+}
+
+const LEFT: Option<Outcome> = Some(Outcome::Winner(Side::Left));
+const RIGHT: Option<Outcome> = Some(Outcome::Winner(Side::Right));
+const DRAW: Option<Outcome> = Some(Outcome::Draw);
+
+#[test]
+fn endless_never_ends() {
+    assert_eq!(game(WinCondition::Endless, 0, 0).outcome(), None);
+    assert_eq!(game(WinCondition::Endless, 1000, 3).outcome(), None);
+}
+
+#[test]
+fn first_to_ends_at_the_score_limit() {
+    assert_eq!(game(WinCondition::FirstTo(5), 4, 4).outcome(), None);
+    assert_eq!(game(WinCondition::FirstTo(5), 5, 4).outcome(), LEFT);
+    assert_eq!(game(WinCondition::FirstTo(5), 2, 5).outcome(), RIGHT);
+}
+
+#[test]
+fn time_limit_goes_to_whoever_is_ahead() {
+    let at = |time: f32, score1: i32, score2: i32| {
+        let mut game = game(WinCondition::TimeLimit(60.0), score1, score2);
+        game.time = time;
+        game.outcome()
+    };
+    assert_eq!(at(59.9, 3, 0), None);
+    assert_eq!(at(60.0, 3, 0), LEFT);
+    assert_eq!(at(60.0, 1, 2), RIGHT);
+    assert_eq!(at(60.0, 2, 2), DRAW);
+    assert_eq!(at(60.0, 0, 0), DRAW);
+}
+
+#[test]
+fn best_of_ends_once_one_player_has_more_than_half() {
+    assert_eq!(game(WinCondition::BestOf(5), 2, 2).outcome(), None);
+    assert_eq!(game(WinCondition::BestOf(5), 3, 0).outcome(), LEFT);
+    assert_eq!(game(WinCondition::BestOf(5), 1, 3).outcome(), RIGHT);
+}
+
+#[test]
+fn even_best_of_can_end_in_a_draw() {
+    assert_eq!(game(WinCondition::BestOf(4), 2, 1).outcome(), None);
+    assert_eq!(game(WinCondition::BestOf(4), 2, 2).outcome(), DRAW);
+    assert_eq!(game(WinCondition::BestOf(4), 3, 0).outcome(), LEFT);
+}
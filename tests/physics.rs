@@ -0,0 +1,72 @@
+//! The same game time should play out the same however it is cut up.
+
+use framslov_melvinj_game::simulation::*;
+
+fn empty_field(substeps: u32) -> Simulation {
+    Simulation::with_config(GameConfig {
+        win_condition: WinCondition::Endless,
+        substeps,
+        rules: GameRules { balls_per_side: 0, ..GameRules::default() },
+        ..GameConfig::default()
+    })
+}
+
+/// Red runs up and right for half a second, then lets go for a second.
+fn run(sim: &mut Simulation, dt: f32) -> (f32, f32) {
+    let steps = (1.5 / dt).round() as usize;
+    for step in 0..steps {
+        let input = if step < steps / 3 {
+            InputState { xaxis1pos: 1.0, yaxis1pos: 1.0, ..InputState::default() }
+        } else {
+            InputState::default()
+        };
+        sim.step(input, InputState::default(), dt);
+    }
+    sim.game.player1.pos
+}
+
+fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.0 - b.0).abs() < 2.0 && (a.1 - b.1).abs() < 2.0
+}
+
+#[test]
+fn players_move_the_same_at_any_tick_rate() {
+    let at_60 = run(&mut empty_field(1), TICK);
+    let at_240 = run(&mut empty_field(1), TICK / 4.0);
+    let substepped = run(&mut empty_field(4), TICK);
+    assert!(close(at_60, at_240), "{:?} at 60 Hz, {:?} at 240 Hz", at_60, at_240);
+    assert!(close(at_60, substepped), "{:?} at 60 Hz, {:?} with 4 substeps", at_60, substepped);
+}
+
+#[test]
+fn speed_is_capped_in_every_direction() {
+    let mut sim = empty_field(1);
+    let diagonal = InputState { xaxis1pos: 1.0, yaxis1pos: 1.0, ..InputState::default() };
+    for _ in 0..120 {
+        sim.tick(diagonal, InputState::default());
+    }
+    let player = &sim.game.player1;
+    let speed = (player.x_velocity * player.x_velocity + player.y_velocity * player.y_velocity).sqrt();
+    assert!(speed <= 200.0 + 1e-3, "going {}", speed);
+}
+
+#[test]
+fn nonsense_input_is_ignored_or_clamped() {
+    let nonsense = InputState {
+        xaxis1pos: f32::NAN,
+        xaxis1neg: f32::NEG_INFINITY,
+        yaxis1pos: 1000.0,
+        yaxis1neg: 5.0,
+        ..InputState::default()
+    };
+    let mut sim = empty_field(1);
+    let mut fair = empty_field(1);
+    let up = InputState { yaxis1pos: 1.0, ..InputState::default() };
+    for _ in 0..60 {
+        sim.tick(nonsense, InputState::default());
+        fair.tick(up, InputState::default());
+    }
+    let player = &sim.game.player1;
+    assert!(player.pos.0.is_finite() && player.pos.1.is_finite(), "ended at {:?}", player.pos);
+    assert_eq!(player.pos, fair.game.player1.pos);
+}
@@ -0,0 +1,63 @@
+//! Changing rules by name, and rules the game refuses to play with.
+
+use framslov_melvinj_game::rules;
+use framslov_melvinj_game::simulation::GameRules;
+
+#[test]
+fn set_changes_one_rule() {
+    let mut rules = GameRules::default();
+    rules::set(&mut rules, "ball_drag", "40").unwrap();
+    rules::set(&mut rules, "balls_per_side", "8").unwrap();
+    assert_eq!(rules.ball_drag, 40.0);
+    assert_eq!(rules.balls_per_side, 8);
+    assert_eq!(GameRules { ball_drag: 40.0, balls_per_side: 8, ..GameRules::default() }, rules);
+}
+
+#[test]
+fn set_refuses_unknown_rules() {
+    let mut rules = GameRules::default();
+    let error = rules::set(&mut rules, "gravity", "9.8").unwrap_err();
+    assert!(error.contains("Unknown rule gravity"), "{}", error);
+    assert_eq!(rules, GameRules::default());
+}
+
+#[test]
+fn set_refuses_values_of_the_wrong_type() {
+    let mut rules = GameRules::default();
+    for (name, value) in [("balls_per_side", "2.5"), ("balls_per_side", "-1"), ("ball_drag", "\"fast\""), ("ball_drag", "fast"), ("restitution", "")] {
+        let error = rules::set(&mut rules, name, value).unwrap_err();
+        assert!(error.contains("Invalid value"), "{} = {}: {}", name, value, error);
+    }
+    assert_eq!(rules, GameRules::default());
+}
+
+#[test]
+fn default_rules_pass_the_check() {
+    assert_eq!(rules::check(&GameRules::default(), 640.0, 480.0), Ok(()));
+}
+
+#[test]
+fn check_refuses_rules_that_make_no_sense() {
+    let bad = [
+        GameRules { ball_radius: 0.0, ..GameRules::default() },
+        GameRules { player_mass: -1.0, ..GameRules::default() },
+        GameRules { max_player_speed: f32::INFINITY, ..GameRules::default() },
+        GameRules { ball_drag: f32::NAN, ..GameRules::default() },
+        GameRules { restitution: 1.5, ..GameRules::default() },
+        GameRules { min_throw_speed: 500.0, max_throw_speed: 400.0, ..GameRules::default() },
+    ];
+    for rules in bad {
+        assert!(rules::check(&rules, 640.0, 480.0).is_err(), "{:?} passed", rules);
+    }
+}
+
+#[test]
+fn check_refuses_more_balls_than_fit() {
+    //Two columns of 7 fit in 480 with the default spacing
+    let balls = |balls_per_side: u32| GameRules { balls_per_side, ..GameRules::default() };
+    assert_eq!(rules::check(&balls(14), 640.0, 480.0), Ok(()));
+    let error = rules::check(&balls(15), 640.0, 480.0).unwrap_err();
+    assert!(error.contains("balls_per_side"), "{}", error);
+    assert!(rules::check(&balls(15), 640.0, 1000.0).is_ok());
+    assert!(rules::check(&balls(u32::MAX), 640.0, 480.0).is_err());
+}
@@ -0,0 +1,139 @@
+//! Fast balls against long steps: nothing may pass through a player or a
+//! wall just because it went past it between two positions.
+
+use framslov_melvinj_game::simulation::*;
+
+/// An empty, endless 640 by 480 field with the players where they are put.
+fn field(player1: (f32, f32), player2: (f32, f32)) -> Simulation {
+    let mut sim = Simulation::with_config(GameConfig {
+        win_condition: WinCondition::Endless,
+        rules: GameRules { balls_per_side: 0, ..GameRules::default() },
+        ..GameConfig::default()
+    });
+    sim.game.player1.pos = player1;
+    sim.game.player2.pos = player2;
+    sim
+}
+
+/// A ball last thrown by `hold`, or resting if it is 0.
+fn ball(id: f32, hold: f32, pos: (f32, f32), velocity: (f32, f32)) -> PhysObject {
+    PhysObject {
+        tag: PhysType::Ball,
+        id,
+        hold,
+        pos,
+        x_velocity: velocity.0,
+        y_velocity: velocity.1,
+        bbox_size: GameRules::default().ball_radius,
+    }
+}
+
+fn step(sim: &mut Simulation, dt: f32) -> Option<Side> {
+    sim.step(InputState::default(), InputState::default(), dt)
+}
+
+#[test]
+fn fast_throw_hits_a_player_it_passes_in_one_step() {
+    //Starts 100 in front of player 2 and ends 125 behind it
+    let mut sim = field((-240.0, 0.0), (150.0, 0.0));
+    sim.game.balls.push(ball(3.0, 1.0, (50.0, 0.0), (450.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.5), Some(Side::Left));
+    assert_eq!((sim.game.score1, sim.game.score2), (1, 0));
+}
+
+#[test]
+fn throw_still_hits_at_the_normal_tick() {
+    let mut sim = field((-240.0, 0.0), (150.0, 0.0));
+    sim.game.balls.push(ball(3.0, 1.0, (100.0, 0.0), (450.0, 0.0)));
+    assert_eq!(sim.tick(InputState::default(), InputState::default()), Some(Side::Left));
+}
+
+#[test]
+fn near_miss_does_not_score() {
+    let mut sim = field((-240.0, 0.0), (150.0, 0.0));
+    sim.game.balls.push(ball(3.0, 1.0, (50.0, 49.0), (450.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.5), None);
+
+    let mut sim = field((-240.0, 0.0), (150.0, 0.0));
+    sim.game.balls.push(ball(3.0, 1.0, (50.0, 47.0), (450.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.5), Some(Side::Left));
+}
+
+#[test]
+fn earlier_hit_wins_when_both_players_are_hit() {
+    //Player 2 is 22 from being hit, player 1 is 52
+    let mut sim = field((-150.0, 0.0), (150.0, 0.0));
+    sim.game.balls.push(ball(3.0, 2.0, (-50.0, 0.0), (-450.0, 0.0)));
+    sim.game.balls.push(ball(4.0, 1.0, (80.0, 0.0), (450.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.5), Some(Side::Left));
+
+    let mut sim = field((-150.0, 0.0), (150.0, 0.0));
+    sim.game.balls.push(ball(3.0, 2.0, (-80.0, 0.0), (-450.0, 0.0)));
+    sim.game.balls.push(ball(4.0, 1.0, (50.0, 0.0), (450.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.5), Some(Side::Right));
+}
+
+#[test]
+fn ball_bounces_off_the_wall_where_it_reaches_it() {
+    //The wall is 320 out, so the edge of the ball reaches it at 296
+    let mut sim = field((-240.0, 0.0), (240.0, 0.0));
+    sim.game.balls.push(ball(3.0, 0.0, (250.0, 120.0), (400.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.25), None);
+    let ball = &sim.game.balls[0];
+    assert!((ball.pos.0 - 242.0).abs() < 1e-3, "ended at {}", ball.pos.0);
+    assert!(ball.x_velocity < 0.0);
+}
+
+#[test]
+fn ball_bounces_out_of_a_corner() {
+    //Reaches both walls at once, 16 out of the corner at 296, 216
+    let mut sim = field((-240.0, 0.0), (240.0, 0.0));
+    sim.game.balls.push(ball(3.0, 0.0, (280.0, 200.0), (300.0, 300.0)));
+    assert_eq!(step(&mut sim, 0.5), None);
+    let ball = &sim.game.balls[0];
+    assert!((ball.pos.0 - 162.0).abs() < 1e-3 && (ball.pos.1 - 82.0).abs() < 1e-3, "ended at {:?}", ball.pos);
+    assert!(ball.x_velocity < 0.0 && ball.y_velocity < 0.0);
+}
+
+#[test]
+fn ball_stays_on_the_field_through_many_bounces() {
+    let mut sim = field((-300.0, -220.0), (300.0, -220.0));
+    sim.game.balls.push(ball(3.0, 0.0, (0.0, 100.0), (450.0, 430.0)));
+    for _ in 0..20 {
+        step(&mut sim, 1.0);
+        let ball = &sim.game.balls[0];
+        assert!(ball.pos.0.abs() <= 296.0 && ball.pos.1.abs() <= 216.0, "left the field at {:?}", ball.pos);
+    }
+}
+
+#[test]
+fn resting_ball_bounces_off_a_player_instead_of_passing_through() {
+    let mut sim = field((-240.0, 0.0), (200.0, 0.0));
+    sim.game.balls.push(ball(3.0, 0.0, (100.0, 0.0), (450.0, 0.0)));
+    assert_eq!(step(&mut sim, 0.5), None);
+    let (ball, player) = (&sim.game.balls[0], &sim.game.player2);
+    assert!(ball.pos.0 < player.pos.0, "ball at {:?}, player at {:?}", ball.pos, player.pos);
+    assert!(ball.x_velocity < 0.0);
+    assert!(player.x_velocity > 0.0);
+}
+
+#[test]
+fn ball_knocked_by_a_throw_hits_the_player_behind_it() {
+    let mut sim = field((-240.0, 0.0), (240.0, 0.0));
+    sim.game.balls.push(ball(3.0, 1.0, (40.0, 0.0), (450.0, 0.0)));
+    sim.game.balls.push(ball(4.0, 0.0, (100.0, 0.0), (0.0, 0.0)));
+    let scored = (0..60).find_map(|_| sim.tick(InputState::default(), InputState::default()));
+    assert_eq!(scored, Some(Side::Left));
+}
+
+#[test]
+fn held_ball_blocks_a_throw() {
+    let mut sim = field((-240.0, 0.0), (240.0, 0.0));
+    sim.game.balls.push(ball(3.0, 1.0, (40.0, 0.0), (450.0, 0.0)));
+    sim.game.balls.push(ball(4.0, 2.0, (208.0, 0.0), (0.0, 0.0)));
+    sim.game.player2.hold = 4.0;
+    let hold = InputState { holdball: true, ..InputState::default() };
+    for _ in 0..60 {
+        assert_eq!(sim.tick(InputState::default(), hold), None);
+    }
+}